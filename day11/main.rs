@@ -1,19 +1,9 @@
 use std::fs::File;
 use intcode::*;
-use std::rc::Rc;
 use std::cell::RefCell;
 use std::fmt;
 use std::result::Result;
 
-struct RobotInput {
-    map: Rc<RefCell<Map>>
-}
-
-struct RobotOutput {
-    map: Rc<RefCell<Map>>,
-    color_output: bool
-}
-
 const MAP_SIZE: usize = 100;
 
 #[derive(Clone, Copy)]
@@ -59,6 +49,26 @@ impl Map {
     fn painted_count(&self) -> usize {
         self.painted.iter().filter(|&x| *x).count()
     }
+
+    // the robot outputs a color to paint and then a direction to turn before moving on
+    fn apply(&mut self, value: i64, color_output: bool) {
+        if color_output {
+            match value {
+                0 => self.paint_cell(false),
+                1 => self.paint_cell(true),
+                _ => panic!("received invalid color")
+            }
+        } else {
+            self.robot_direction = match (value, self.robot_direction) {
+                (0, Direction::Up) | (1, Direction::Down) => Direction::Left,
+                (0, Direction::Down) | (1, Direction::Up) => Direction::Right,
+                (0, Direction::Left) | (1, Direction::Right) => Direction::Down,
+                (0, Direction::Right) | (1, Direction::Left) => Direction::Up,
+                _ => panic!("received invalid direction")
+            };
+            self.move_forward();
+        }
+    }
 }
 
 impl fmt::Display for Map {
@@ -73,66 +83,28 @@ impl fmt::Display for Map {
     }
 }
 
-impl Input for RobotInput {
-    fn get_next(&mut self) -> i64 {
-        if self.map.borrow().current_cell() { 1 } else { 0 }
-    }
-}
-
-impl Output for RobotOutput {
-    fn output(&mut self, value: i64) {
-        if self.color_output {
-            // receive color output
-            match value {
-                0 => self.map.borrow_mut().paint_cell(false),
-                1 => self.map.borrow_mut().paint_cell(true),
-                _ => panic!("received invalid color")
-            }
-        } else {
-            // receive direction output
-            let current_direction = self.map.borrow().robot_direction;
-            match value {
-                0 => {
-                    self.map.borrow_mut().robot_direction = match current_direction {
-                        Direction::Up => Direction::Left,
-                        Direction::Down => Direction::Right,
-                        Direction::Left => Direction::Down,
-                        Direction::Right => Direction::Up
-                    };
-                },
-                1 => {
-                    self.map.borrow_mut().robot_direction = match current_direction {
-                        Direction::Up => Direction::Right,
-                        Direction::Down => Direction::Left,
-                        Direction::Left => Direction::Up,
-                        Direction::Right => Direction::Down
-                    };
-                },
-                _ => panic!("received invalid direction")
-            }
-            self.map.borrow_mut().move_forward();
-        }
-        self.color_output = !self.color_output;
-    }
+fn paint(tape: &[i64], map: Map) -> Map {
+    let map = RefCell::new(map);
+    let mut color_output = true;
+    execute_intcode(tape, &mut input_fn(|| if map.borrow().current_cell() { 1 } else { 0 }), &mut |value| {
+        map.borrow_mut().apply(value, color_output);
+        color_output = !color_output;
+    });
+    map.into_inner()
 }
 
 fn main() {
     let input_file = File::open("input.txt").unwrap();
     let tape = load_tape(input_file);
 
-    let map = Rc::new(RefCell::new(Map::new()));
-    let mut input = RobotInput { map: Rc::clone(&map) };
-    let mut output = RobotOutput { map: Rc::clone(&map), color_output: true };
-    execute_intcode(&tape, &mut input, &mut output);
+    let map = paint(&tape, Map::new());
     println!("Part 1:");
-    println!("{}", map.borrow());
-    println!("{}", map.borrow().painted_count());
+    println!("{}", map);
+    println!("{}", map.painted_count());
 
-    let map = Rc::new(RefCell::new(Map::new()));
-    map.borrow_mut().paint_cell(true);
-    let mut input = RobotInput { map: Rc::clone(&map) };
-    let mut output = RobotOutput { map: Rc::clone(&map), color_output: true };
-    execute_intcode(&tape, &mut input, &mut output);
+    let mut map = Map::new();
+    map.paint_cell(true);
+    let map = paint(&tape, map);
     println!("Part 2:");
-    println!("{}", map.borrow());
-}
\ No newline at end of file
+    println!("{}", map);
+}
@@ -125,6 +125,72 @@ impl Input for ArcadeInput {
     }
 }
 
+// Plays the game from the screen contents and narrows down the cells that hold the ball x, paddle x and score by
+// keeping only those that match the screen every time the game asks for input. Returns None if the game does not
+// draw a ball and a paddle before it asks for input, or if it halts before every value is down to a single cell.
+fn locate_state(tape: &[i64]) -> Option<(usize, usize, usize)> {
+    let screen = Rc::new(RefCell::new(ArcadeScreen::new()));
+    let mut screen_input = ArcadeScreenInput::new(Rc::clone(&screen));
+    let mut joystick = ArcadeInput::new(Rc::clone(&screen));
+    let mut machine = Machine::new(tape);
+    let mut candidates: Vec<Option<Vec<usize>>> = vec![None, None, None];
+    loop {
+        match machine.run().ok()? {
+            Status::Output(value) => screen_input.output(value),
+            Status::NeedsInput => {
+                let screen = screen.borrow();
+                let x = |tile| screen.tiles.iter().position(|&t| t == tile).map(|position| (position % SCREEN_WIDTH) as i64);
+                let values = [x(Tile::Ball)?, x(Tile::Paddle)?, screen.score];
+                let cells = machine.memory().cells();
+                for (candidates, &value) in candidates.iter_mut().zip(values.iter()) {
+                    let matching = candidates.take().unwrap_or_else(|| (0..cells.len()).collect());
+                    *candidates = Some(matching.into_iter().filter(|&address| cells[address] == value).collect());
+                }
+                if candidates.iter().all(|c| c.as_ref().is_some_and(|c| c.len() == 1)) {
+                    break;
+                }
+                machine.push_input(joystick.get_next());
+            },
+            // on a halt, guessing between cells that matched all the time could give a wrong score
+            _ => return None
+        }
+    }
+    let found: Vec<usize> = candidates.into_iter().map(|c| c.unwrap()[0]).collect();
+    Some((found[0], found[1], found[2]))
+}
+
+// the outputs are ignored, the joystick follows the watched cells
+fn play_watched(tape: &[i64], (ball, paddle, score): (usize, usize, usize)) -> i64 {
+    let mut machine = Machine::new(tape);
+    machine.add_watchpoint(ball);
+    machine.add_watchpoint(paddle);
+    machine.add_watchpoint(score);
+    let mut ball_x = machine.memory()[ball];
+    let mut paddle_x = machine.memory()[paddle];
+    loop {
+        match machine.run().unwrap() {
+            Status::Watchpoint(write) if write.address == ball => ball_x = write.new,
+            Status::Watchpoint(write) if write.address == paddle => paddle_x = write.new,
+            Status::Watchpoint(_) | Status::Output(_) => {},
+            Status::NeedsInput => machine.push_input((ball_x - paddle_x).signum()),
+            Status::Halted => break,
+            status => panic!("unexpected status {:?}", status)
+        }
+    }
+    machine.memory()[score]
+}
+
+// draws every output triple and moves the joystick from the screen
+fn play_screen(tape: &[i64]) -> i64 {
+    let screen = Rc::new(RefCell::new(ArcadeScreen::new()));
+    let mut input = ArcadeScreenInput::new(Rc::clone(&screen));
+    let mut output = ArcadeInput::new(Rc::clone(&screen));
+    execute_intcode(tape, &mut output, &mut input);
+    println!("{}", screen.borrow());
+    let score = screen.borrow().score;
+    score
+}
+
 fn main() {
     let input_file = File::open("input.txt").unwrap();
     let mut tape = load_tape(input_file);
@@ -135,9 +201,12 @@ fn main() {
     println!("Part 1: {}", screen.borrow().tiles.iter().filter(|&tile| *tile == Tile::Block).count());
 
     tape[0] = 2;
-    let screen = Rc::new(RefCell::new(ArcadeScreen::new()));
-    let mut input = ArcadeScreenInput::new(Rc::clone(&screen));
-    let mut output = ArcadeInput::new(Rc::clone(&screen));
-    execute_intcode(&tape, &mut output, &mut input);
-    println!("{}", screen.borrow());
-}
\ No newline at end of file
+    let score = match locate_state(&tape) {
+        Some(cells) => {
+            println!("Ball x at {}, paddle x at {}, score at {}", cells.0, cells.1, cells.2);
+            play_watched(&tape, cells)
+        },
+        None => play_screen(&tape)
+    };
+    println!("Part 2: {}", score);
+}
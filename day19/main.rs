@@ -1,10 +1,32 @@
+use std::env;
 use std::fs::File;
 use intcode::*;
+use intcode::cache::execute_intcode_cached;
+use intcode::cache::try_execute_intcode_cached_observed;
 
+// runs one drone on a fresh VM, counted as an instance of the profile if there is one
+fn deploy_drone(tape: &[i64], cache: &DecodeCache, input: &mut VecInput, output: &mut VecOutput,
+    profile: &mut Option<Profile>) {
+    match profile {
+        Some(profile) => {
+            profile.add_instance();
+            if let Err(error) = try_execute_intcode_cached_observed(tape, cache, input, output, profile) {
+                panic!("{}", error);
+            }
+        },
+        None => { execute_intcode_cached(tape, cache, input, output); }
+    }
+}
 
 fn main() {
     let input_file = File::open("input.txt").unwrap();
     let tape = load_tape(input_file);
+    let cache = DecodeCache::for_tape(&tape);
+    // profiling slows every instruction down, so it only runs with --profile
+    let mut profile = if env::args().any(|arg| arg == "--profile") { Some(Profile::new()) } else { None };
+    if let Some(profile) = &mut profile {
+        profile.phase("part 1");
+    }
 
     let mut affected_count = 0;
 
@@ -12,11 +34,14 @@ fn main() {
         for y in 0..50 {
             let mut output = VecOutput::new();
             let mut input = VecInput::new(vec![x, y]);
-            execute_intcode(&tape, &mut input, &mut output);
+            deploy_drone(&tape, &cache, &mut input, &mut output, &mut profile);
             affected_count += output.values()[0];
         }
     }
     println!("Part 1: {}", affected_count);
+    if let Some(profile) = &mut profile {
+        profile.phase("part 2");
+    }
 
     let mut found = false;
     let mut found_x = 0;
@@ -33,10 +58,10 @@ fn main() {
             let b_y = y + SIZE - 1;
             let mut output = VecOutput::new();
             let mut input = VecInput::new(vec![x, y, x, b_y, r_x, y, r_x, b_y]);
-            execute_intcode(&tape, &mut input, &mut output);
-            execute_intcode(&tape, &mut input, &mut output);
-            execute_intcode(&tape, &mut input, &mut output);
-            execute_intcode(&tape, &mut input, &mut output);
+            deploy_drone(&tape, &cache, &mut input, &mut output, &mut profile);
+            deploy_drone(&tape, &cache, &mut input, &mut output, &mut profile);
+            deploy_drone(&tape, &cache, &mut input, &mut output, &mut profile);
+            deploy_drone(&tape, &cache, &mut input, &mut output, &mut profile);
             if output.values()[0] == 1 && output.values()[1] == 1 && output.values()[2] == 1 && output.values()[3] == 1 {
                 found = true;
                 found_x = x;
@@ -51,4 +76,9 @@ fn main() {
         dist += 1;
     }
     println!("Part 2: {}", found_x * 10000 + found_y);
+    if let Some(profile) = &mut profile {
+        profile.end_phase();
+        eprintln!("{}", profile.summary("day19"));
+        eprint!("{}", profile);
+    }
 }
\ No newline at end of file
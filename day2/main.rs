@@ -10,13 +10,15 @@ fn main() {
 
     println!("Part 1: {}", execute_intcode(&tape, &mut StdInput, &mut StdOutput)[0]);
 
-    for noun in 0..99 {
-        for verb in 0..99 {       
-            tape[1] = noun;
-            tape[2] = verb;
-            if execute_intcode(&tape, &mut StdInput, &mut StdOutput)[0] == 19690720 {
-                println!("Part 2: {}", 100 * noun + verb);
-            }
-        }
+    let mut search = Search::new();
+    search.add_cell(1, 0..=99);
+    search.add_cell(2, 0..=99);
+    let found = search.find_first(|candidate| candidate.run(&tape).is_ok_and(|run| run.memory[0] == 19690720));
+    match found {
+        Some(candidate) => {
+            let (noun, verb) = (candidate.patches[0].1, candidate.patches[1].1);
+            println!("Part 2: {}", 100 * noun + verb);
+        },
+        None => println!("Part 2: no noun and verb produce 19690720")
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::time::Duration;
+use std::time::Instant;
+use intcode::*;
+use intcode::asm::assemble;
+use intcode::cache::execute_intcode_cached;
+
+// sums i * j for all i, j below the input value, entirely in positional mode so the loop body touches memory a lot
+const NESTED_LOOPS: &str = "
+            IN -> [n]
+    outer:  ADD #0, #0 -> [j]
+    inner:  MUL [i], [j] -> [tmp]
+            ADD [sum], [tmp] -> [sum]
+            ADD [j], #1 -> [j]
+            LT [j], [n] -> [flag]
+            JT [flag], #inner
+            ADD [i], #1 -> [i]
+            LT [i], [n] -> [flag]
+            JT [flag], #outer
+            OUT [sum]
+            HLT
+    n:      DATA 0
+    i:      DATA 0
+    j:      DATA 0
+    tmp:    DATA 0
+    sum:    DATA 0
+    flag:   DATA 0
+";
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn compare(name: &str, tape: &[i64], runs: &[Vec<i64>]) {
+    let cache = DecodeCache::for_tape(tape);
+    let mut expected = VecOutput::new();
+    let mut actual = VecOutput::new();
+    let interpreted = time(|| for inputs in runs {
+        execute_intcode(tape, &mut VecInput::new(inputs.clone()), &mut expected);
+    });
+    let cached = time(|| for inputs in runs {
+        execute_intcode_cached(tape, &cache, &mut VecInput::new(inputs.clone()), &mut actual);
+    });
+    assert_eq!(expected.values(), actual.values(), "{}: cached run produced different outputs", name);
+    println!("{:<14} {:>6} run(s)  interpreter {:>10.2?}  cached {:>10.2?}  speedup {:.2}x", name, runs.len(), interpreted,
+        cached, interpreted.as_secs_f64() / cached.as_secs_f64());
+}
+
+fn day_tape(day: u32) -> Option<Vec<i64>> {
+    let path = format!("{}/../day{}/input.txt", env!("CARGO_MANIFEST_DIR"), day);
+    match File::open(&path) {
+        Ok(file) => Some(load_tape(file)),
+        Err(_) => {
+            println!("{:<14} skipped, {} not found", format!("day{}", day), path);
+            None
+        }
+    }
+}
+
+fn main() {
+    compare("nested loops", &assemble(NESTED_LOOPS).unwrap(), &[vec![2000]]);
+    if let Some(tape) = day_tape(5) {
+        compare("day5", &tape, &vec![vec![5]; 1000]);
+    }
+    if let Some(tape) = day_tape(9) {
+        compare("day9", &tape, &[vec![2]]);
+    }
+    if let Some(tape) = day_tape(19) {
+        let runs: Vec<Vec<i64>> = (0..50).flat_map(|x| (0..50).map(move |y| vec![x, y])).collect();
+        compare("day19", &tape, &runs);
+    }
+}
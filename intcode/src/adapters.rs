@@ -0,0 +1,158 @@
+use crate::Cell;
+use crate::Input;
+use crate::Output;
+
+// Any iterator is an input that runs out when the iterator does, so a vec works as vec.into_iter() and a fixed prefix
+// as prefix.iter().copied(). Closures can not be inputs directly, as a type could be both; see input_fn.
+impl<C: Cell, I: Iterator<Item = C>> Input<C> for I {
+    fn get_next(&mut self) -> C {
+        self.next().expect("iterator ran out of inputs")
+    }
+
+    fn try_get_next(&mut self) -> Option<C> {
+        self.next()
+    }
+}
+
+// any closure taking a value is an output
+impl<C: Cell, F: FnMut(C)> Output<C> for F {
+    fn output(&mut self, value: C) {
+        self(value)
+    }
+}
+
+// calls the closure for every value, it never runs out
+pub struct FnInput<F> {
+    f: F
+}
+
+pub fn input_fn<C: Cell, F: FnMut() -> C>(f: F) -> FnInput<F> {
+    FnInput { f }
+}
+
+impl<C: Cell, F: FnMut() -> C> Input<C> for FnInput<F> {
+    fn get_next(&mut self) -> C {
+        (self.f)()
+    }
+}
+
+// adds every value to a collection, e.g. a Vec or a HashSet
+pub struct ExtendOutput<'a, E> {
+    target: &'a mut E
+}
+
+impl<'a, E> ExtendOutput<'a, E> {
+    pub fn new(target: &'a mut E) -> ExtendOutput<'a, E> {
+        ExtendOutput { target }
+    }
+}
+
+// fixed to i64, since most collections can be extended by values as well as references
+pub fn extend_output<E: Extend<i64>>(target: &mut E) -> ExtendOutput<'_, E> {
+    ExtendOutput::new(target)
+}
+
+impl<'a, C: Cell, E: Extend<C>> Output<C> for ExtendOutput<'a, E> {
+    fn output(&mut self, value: C) {
+        self.target.extend(Some(value));
+    }
+}
+
+// reads from the first input until it runs out, then from the second
+pub struct Chain<A, B> {
+    first: Option<A>,
+    second: B
+}
+
+impl<A, B> Chain<A, B> {
+    pub(crate) fn new(first: A, second: B) -> Chain<A, B> {
+        Chain { first: Some(first), second }
+    }
+}
+
+impl<C: Cell, A: Input<C>, B: Input<C>> Input<C> for Chain<A, B> {
+    fn get_next(&mut self) -> C {
+        match self.try_get_next() {
+            Some(value) => value,
+            None => self.second.get_next()
+        }
+    }
+
+    fn try_get_next(&mut self) -> Option<C> {
+        if let Some(first) = &mut self.first {
+            match first.try_get_next() {
+                Some(value) => return Some(value),
+                None => self.first = None
+            }
+        }
+        self.second.try_get_next()
+    }
+}
+
+// writes every value to both outputs
+pub struct Tee<A, B> {
+    first: A,
+    second: B
+}
+
+impl<A, B> Tee<A, B> {
+    pub(crate) fn new(first: A, second: B) -> Tee<A, B> {
+        Tee { first, second }
+    }
+}
+
+impl<C: Cell, A: Output<C>, B: Output<C>> Output<C> for Tee<A, B> {
+    fn output(&mut self, value: C) {
+        self.first.output(value.clone());
+        self.second.output(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use crate::adapters::extend_output;
+    use crate::adapters::input_fn;
+    use crate::execute_intcode;
+    use crate::try_execute_intcode;
+    use crate::Input;
+    use crate::IntcodeError;
+    use crate::Machine;
+    use crate::Output;
+    use crate::Status;
+
+    // adds one to every input until it reads a zero
+    const TAPE: [i64; 16] = [3, 15, 1006, 15, 14, 1001, 15, 1, 15, 4, 15, 1105, 1, 0, 99, 0];
+
+    #[test]
+    fn closures_and_collections() {
+        let mut outputs = Vec::new();
+        execute_intcode(&TAPE, &mut vec![1, 2, 0].into_iter(), &mut |value| outputs.push(value));
+        assert_eq!(outputs, vec![2, 3]);
+
+        // a fixed prefix, then an interactive source that counts down
+        let mut countdown = 3;
+        let mut input = [7, 7].iter().copied().followed_by(input_fn(|| {
+            countdown -= 1;
+            countdown
+        }));
+        let mut set = BTreeSet::new();
+        let mut last = 0;
+        execute_intcode(&TAPE, &mut input, &mut extend_output(&mut set).tee(|value| last = value));
+        assert_eq!((set.into_iter().collect::<Vec<i64>>(), last), (vec![2, 3, 8], 2));
+
+        assert!(matches!(try_execute_intcode(&TAPE, &mut [4].iter().copied(), &mut |_| {}),
+            Err(IntcodeError::InputExhausted { .. })));
+    }
+
+    #[test]
+    fn machine_outputs() {
+        let mut machine = Machine::new(&TAPE);
+        machine.extend_input(&[1, 2]);
+        assert_eq!(machine.outputs().collect::<Vec<i64>>(), vec![2, 3]);
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        machine.extend_input(&[5, 0]);
+        assert_eq!(machine.outputs().sum::<i64>(), 6);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use crate::isa::Definition;
+use crate::InstructionSet;
+use crate::Mode;
+use crate::Opcode;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for AsmError {}
+
+#[derive(Clone, Debug)]
+struct Expr {
+    label: Option<String>,
+    offset: i64
+}
+
+impl Expr {
+    fn value(offset: i64) -> Expr {
+        Expr { label: None, offset }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Operand {
+    mode: Mode,
+    expr: Expr
+}
+
+enum Statement {
+    Instruction { definition: Definition, operands: Vec<Operand> },
+    Data(Vec<Expr>)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+fn parse_expr(s: &str) -> Result<Expr, String> {
+    let s = s.trim();
+    if let Ok(value) = s.parse::<i64>() {
+        return Ok(Expr::value(value));
+    }
+    let (label, offset) = match s.find(['+', '-']) {
+        Some(i) => {
+            let offset = s[i + 1..].trim().parse::<i64>().map_err(|_| format!("invalid offset in '{}'", s))?;
+            (s[..i].trim(), if &s[i..i + 1] == "-" { -offset } else { offset })
+        },
+        None => (s, 0)
+    };
+    if !is_identifier(label) {
+        return Err(format!("invalid expression '{}'", s));
+    }
+    Ok(Expr { label: Some(label.to_string()), offset })
+}
+
+fn parse_operand(s: &str) -> Result<Operand, String> {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix('#') {
+        return Ok(Operand { mode: Mode::Immediate, expr: parse_expr(rest)? });
+    }
+    if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim();
+        if inner.len() >= 2 && inner[..2].eq_ignore_ascii_case("rb") {
+            let rest = inner[2..].trim();
+            let expr = if rest.is_empty() {
+                Expr::value(0)
+            } else if let Some(offset) = rest.strip_prefix('+') {
+                parse_expr(offset)?
+            } else if rest.starts_with('-') {
+                parse_expr(rest)?
+            } else {
+                return Err(format!("invalid relative operand '{}'", s));
+            };
+            return Ok(Operand { mode: Mode::Relative, expr });
+        }
+        return Ok(Operand { mode: Mode::Position, expr: parse_expr(inner)? });
+    }
+    Err(format!("operand '{}' needs an addressing mode ('#x', '[x]' or '[rb+x]')", s))
+}
+
+fn parse_operands(s: &str) -> Result<Vec<Operand>, String> {
+    if s.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    s.split(',').map(parse_operand).collect()
+}
+
+fn instruction(opcode: Opcode, operands: Vec<Operand>) -> Statement {
+    Statement::Instruction { definition: Definition::standard(opcode), operands }
+}
+
+fn immediate(value: i64) -> Operand {
+    Operand { mode: Mode::Immediate, expr: Expr::value(value) }
+}
+
+fn stack_top() -> Operand {
+    Operand { mode: Mode::Relative, expr: Expr::value(0) }
+}
+
+// the relative-base helpers treat the relative base as a stack pointer to the next free cell
+fn expand_helper(name: &str, operands: Vec<Operand>, address: usize) -> Result<Option<Vec<Statement>>, String> {
+    let expect = |count: usize| if operands.len() == count {
+        Ok(())
+    } else {
+        Err(format!("{} expects {} operand(s), got {}", name, count, operands.len()))
+    };
+    let statements = match name.to_ascii_uppercase().as_str() {
+        "PUSH" => {
+            expect(1)?;
+            vec![instruction(Opcode::Add, vec![operands[0].clone(), immediate(0), stack_top()]),
+                instruction(Opcode::AdjustRelativeBase, vec![immediate(1)])]
+        },
+        "POP" => {
+            expect(1)?;
+            vec![instruction(Opcode::AdjustRelativeBase, vec![immediate(-1)]),
+                instruction(Opcode::Add, vec![stack_top(), immediate(0), operands[0].clone()])]
+        },
+        "CALL" => {
+            expect(1)?;
+            let return_address = address as i64 + 9;
+            vec![instruction(Opcode::Add, vec![immediate(return_address), immediate(0), stack_top()]),
+                instruction(Opcode::AdjustRelativeBase, vec![immediate(1)]),
+                instruction(Opcode::JumpIfTrue, vec![immediate(1), operands[0].clone()])]
+        },
+        "RET" => {
+            expect(0)?;
+            vec![instruction(Opcode::AdjustRelativeBase, vec![immediate(-1)]),
+                instruction(Opcode::JumpIfTrue, vec![immediate(1), stack_top()])]
+        },
+        _ => return Ok(None)
+    };
+    Ok(Some(statements))
+}
+
+fn statement_len(statement: &Statement) -> usize {
+    match statement {
+        Statement::Instruction { operands, .. } => operands.len() + 1,
+        Statement::Data(values) => values.len()
+    }
+}
+
+fn parse_statement(text: &str, address: usize, set: &InstructionSet) -> Result<Vec<Statement>, String> {
+    let (name, rest) = match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, "")
+    };
+    if name.eq_ignore_ascii_case("data") {
+        return Ok(vec![Statement::Data(rest.split(',').map(parse_expr).collect::<Result<_, _>>()?)]);
+    }
+    let (reads, write) = match rest.find("->") {
+        Some(i) => (&rest[..i], Some(&rest[i + 2..])),
+        None => (rest, None)
+    };
+    let mut operands = parse_operands(reads)?;
+    if let Some(write) = write {
+        operands.push(parse_operand(write)?);
+    }
+    if let Some(statements) = expand_helper(name, operands.clone(), address)? {
+        return Ok(statements);
+    }
+    let definition: &Definition = set.definitions().find(|definition| definition.mnemonic.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown mnemonic '{}'", name))?;
+    let mnemonic = definition.mnemonic;
+    if operands.len() != definition.arity {
+        return Err(format!("{} expects {} operand(s), got {}", mnemonic, definition.arity, operands.len()));
+    }
+    if write.is_some() && !definition.writes.contains(&(definition.arity - 1)) {
+        return Err(format!("{} does not write to memory", mnemonic));
+    }
+    if definition.writes.iter().any(|&i| operands[i].mode == Mode::Immediate) {
+        return Err(format!("the target of {} can not be immediate", mnemonic));
+    }
+    Ok(vec![Statement::Instruction { definition: definition.clone(), operands }])
+}
+
+fn resolve(expr: &Expr, labels: &HashMap<String, usize>) -> Result<i64, String> {
+    match &expr.label {
+        Some(label) => match labels.get(label) {
+            Some(address) => Ok(*address as i64 + expr.offset),
+            None => Err(format!("undefined label '{}'", label))
+        },
+        None => Ok(expr.offset)
+    }
+}
+
+// Syntax, one statement per line:
+//   loop: ADD [rb-3], #5 -> [counter]   ; comment
+//   counter: DATA 0, 1, loop+2
+// '#x' is immediate, '[x]' positional and '[rb+x]' relative. Numeric labels as produced by the disassembler
+// ("0012:") assert the current address. PUSH, POP, CALL and RET expand into relative-base stack operations.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    assemble_with(source, &InstructionSet::standard())
+}
+
+// also accepts the mnemonics of custom instructions in the set; the stack helpers always use standard opcodes
+pub fn assemble_with(source: &str, set: &InstructionSet) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+    for (i, line) in source.lines().enumerate() {
+        let error = |message: String| AsmError { line: i + 1, message };
+        let mut text = line.split(';').next().unwrap().trim();
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if let Ok(expected) = label.parse::<usize>() {
+                if expected != address {
+                    return Err(error(format!("address {} expected, but statement is at {}", expected, address)));
+                }
+            } else if is_identifier(label) {
+                if labels.insert(label.to_string(), address).is_some() {
+                    return Err(error(format!("duplicate label '{}'", label)));
+                }
+            } else {
+                break;
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        for statement in parse_statement(text, address, set).map_err(error)? {
+            address += statement_len(&statement);
+            statements.push((i + 1, statement));
+        }
+    }
+
+    let mut tape = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        let error = |message: String| AsmError { line, message };
+        match statement {
+            Statement::Instruction { definition, operands } => {
+                let modes: Vec<Mode> = operands.iter().map(|operand| operand.mode).collect();
+                tape.push(definition.encode(&modes));
+                for operand in &operands {
+                    tape.push(resolve(&operand.expr, &labels).map_err(error)?);
+                }
+            },
+            Statement::Data(values) => {
+                for value in &values {
+                    tape.push(resolve(value, &labels).map_err(error)?);
+                }
+            }
+        }
+    }
+    Ok(tape)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::asm::AsmError;
+    use crate::disasm::listing;
+    use crate::execute_intcode;
+    use crate::StdInput;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    #[test]
+    fn labels_and_data() {
+        let source = "
+            ; counts down from the input to zero
+                    IN -> [counter]
+            loop:   OUT [counter]
+                    ADD [counter], #-1 -> [counter]
+                    JT [counter], #loop
+                    HLT
+            counter: data 0
+        ";
+        let tape = assemble(source).unwrap();
+        assert_eq!(tape, vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0]);
+        let mut out = VecOutput::new();
+        execute_intcode(&tape, &mut VecInput::new(vec![3]), &mut out);
+        assert_eq!(out.values(), &vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn stack_helpers() {
+        let source = "
+                    ARB #stack
+                    CALL #double
+                    OUT [rb-1]
+                    HLT
+            double: ADD #21, #21 -> [rb+0]
+                    POP -> [tmp]
+                    PUSH #2
+                    PUSH [tmp]
+                    RET
+            tmp:    DATA 0
+            stack:  DATA 0, 0, 0, 0
+        ";
+        let tape = assemble(source).unwrap();
+        let mut out = VecOutput::new();
+        execute_intcode(&tape, &mut StdInput, &mut out);
+        assert_eq!(out.values(), &vec![2]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(assemble("ADD #1, #2 -> #3"), Err(AsmError { line: 1,
+            message: String::from("the target of ADD can not be immediate") }));
+        assert_eq!(assemble("HLT\nJT #1, #nowhere"), Err(AsmError { line: 2,
+            message: String::from("undefined label 'nowhere'") }));
+        assert_eq!(assemble("0001: HLT"), Err(AsmError { line: 1,
+            message: String::from("address 1 expected, but statement is at 0") }));
+        assert!(assemble("FOO #1").is_err());
+        assert!(assemble("OUT 5").is_err());
+    }
+
+    #[test]
+    fn disassembly_round_trip() {
+        let tapes = vec![
+            vec![1, 0, 0, 3],
+            vec![1, 0, 0, 3, 99],
+            vec![1101, 1, 1, 3, 99],
+            vec![2, 0, 0, 3, 99],
+            vec![1102, 5, 2, 3, 99],
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+            vec![104, 1125899906842624, 99],
+            vec![109, 15, 109, 19, 204, -34, 99],
+            vec![3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6,
+                99, 0, 0, 5],
+            vec![1104, 3, 1, 0]
+        ];
+        for tape in tapes {
+            assert_eq!(assemble(&listing(&tape)).unwrap(), tape);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+use crate::cell::Cell;
+
+// Arbitrary-precision integer with just the operations Intcode needs: addition, multiplication and comparison.
+// The magnitude is stored as little-endian base 2^32 digits without leading zeros, so zero has no digits and is
+// never negative, which keeps the derived equality and hashing correct.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>
+}
+
+fn normalize(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    normalize(result)
+}
+
+// requires a >= b
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut difference = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if difference < 0 { 1 } else { 0 };
+        if difference < 0 {
+            difference += 1 << 32;
+        }
+        result.push(difference as u32);
+    }
+    normalize(result)
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let product = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    normalize(result)
+}
+
+// divides in place and returns the remainder
+fn div_small(digits: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for digit in digits.iter_mut().rev() {
+        let value = (remainder << 32) | *digit as u64;
+        *digit = (value / divisor as u64) as u32;
+        remainder = value % divisor as u64;
+    }
+    *digits = normalize(std::mem::take(digits));
+    remainder as u32
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, digits: Vec<u32>) -> BigInt {
+        let digits = normalize(digits);
+        BigInt { negative: negative && !digits.is_empty(), digits }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_magnitudes(&self.digits, &other.digits));
+        }
+        match compare_magnitudes(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_magnitudes(&other.digits, &self.digits)),
+            _ => BigInt::from_parts(self.negative, sub_magnitudes(&self.digits, &other.digits))
+        }
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != other.negative, mul_magnitudes(&self.digits, &other.digits))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let magnitude = value.unsigned_abs();
+        BigInt::from_parts(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.digits, &other.digits),
+            (true, true) => compare_magnitudes(&other.digits, &self.digits)
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut digits = self.digits.clone();
+        let mut chunks = Vec::new();
+        while !digits.is_empty() {
+            chunks.push(div_small(&mut digits, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match chunks.pop() {
+            Some(first) => write!(f, "{}", first)?,
+            None => write!(f, "0")?
+        }
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseBigIntError {
+    pub text: String
+}
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid integer: '{}'", self.text)
+    }
+}
+
+impl error::Error for ParseBigIntError {}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<BigInt, ParseBigIntError> {
+        let error = || ParseBigIntError { text: s.to_string() };
+        let (negative, decimal) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s))
+        };
+        if decimal.is_empty() || !decimal.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error());
+        }
+        let mut digits = Vec::new();
+        for b in decimal.bytes() {
+            digits = add_magnitudes(&mul_magnitudes(&digits, &[10]), &[(b - b'0') as u32]);
+        }
+        Ok(BigInt::from_parts(negative, digits))
+    }
+}
+
+impl Cell for BigInt {
+    fn from_i64(value: i64) -> BigInt {
+        BigInt::from(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let magnitude = self.digits.iter().rev().fold(0i128, |value, digit| (value << 32) | *digit as i128);
+        let value = if self.negative { -magnitude } else { magnitude };
+        if value >= i64::MIN as i128 && value <= i64::MAX as i128 { Some(value as i64) } else { None }
+    }
+
+    fn try_add(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self.add(other))
+    }
+
+    fn try_mul(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self.mul(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bigint::BigInt;
+    use crate::cell::Cell;
+
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.mul(&b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+        assert_eq!(b.add(&big("987654321098765432109876543210")), BigInt::default());
+        assert_eq!(big("-0").to_string(), "0");
+        assert!(b < a && big("-2") < big("-1") && big("4294967296") > big("4294967295"));
+        assert!("12a".parse::<BigInt>().is_err());
+        assert!("-".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn i64_conversion() {
+        for value in &[0, 1, -1, 4294967296, i64::MAX, i64::MIN] {
+            let converted = BigInt::from_i64(*value);
+            assert_eq!(converted.to_string(), value.to_string());
+            assert_eq!(converted.to_i64(), Some(*value));
+        }
+        assert_eq!(BigInt::from_i64(i64::MAX).add(&BigInt::from_i64(1)).to_i64(), None);
+        assert_eq!(BigInt::from_i64(i64::MIN).add(&BigInt::from_i64(-1)).clamp_to_i64(), i64::MIN);
+    }
+}
@@ -0,0 +1,15 @@
+use std::env;
+use std::fs;
+use intcode::asm::assemble;
+
+fn main() {
+    let path = env::args().nth(1).expect("usage: asm <source file>");
+    let source = fs::read_to_string(&path).unwrap_or_else(|_| panic!("failed to read {}", path));
+    match assemble(&source) {
+        Ok(tape) => println!("{}", tape.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",")),
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::env;
+use std::fs::File;
+use intcode::load_tape;
+use intcode::coverage::Coverage;
+use intcode::try_execute_intcode_observed;
+use intcode::VecInput;
+use intcode::VecOutput;
+
+// usage: coverage [--csv|--json|--listing] <tape> [input values...]
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let format = if args.first().is_some_and(|arg| arg.starts_with("--")) { args.remove(0) } else { String::new() };
+    let path = args.first().cloned().unwrap_or_else(|| String::from("input.txt"));
+    let input_file = File::open(&path).unwrap_or_else(|_| panic!("failed to open {}", path));
+    let tape = load_tape(input_file);
+    let inputs: Vec<i64> = args.iter().skip(1).map(|arg| arg.parse().expect("invalid input value")).collect();
+
+    // the program's own outputs go to stderr, so that stdout only holds the report
+    let mut coverage = Coverage::new(tape.len());
+    let mut output = VecOutput::new();
+    let result = try_execute_intcode_observed(&tape, &mut VecInput::new(inputs), &mut output, &mut coverage);
+    for value in output.values() {
+        eprintln!("{}", value);
+    }
+    if let Err(error) = result {
+        eprintln!("{}", error);
+    }
+    match format.as_str() {
+        "--csv" => print!("{}", coverage.to_csv()),
+        "--json" => print!("{}", coverage.to_json()),
+        "--listing" => print!("{}", coverage.listing(&tape)),
+        "" => print!("{}", coverage.to_text()),
+        _ => panic!("unknown format {}", format)
+    }
+}
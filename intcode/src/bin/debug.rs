@@ -0,0 +1,19 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use intcode::load_tape;
+use intcode::debugger::Debugger;
+use intcode::StdOutput;
+use intcode::VecInput;
+
+// usage: debug <tape> [input values...], the debugger commands are read from stdin
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| String::from("input.txt"));
+    let input_file = File::open(&path).unwrap_or_else(|_| panic!("failed to open {}", path));
+    let tape = load_tape(input_file);
+    let inputs: Vec<i64> = env::args().skip(2).map(|arg| arg.parse().expect("invalid input value")).collect();
+
+    let mut debugger = Debugger::new(&tape, VecInput::new(inputs), StdOutput);
+    let stdin = io::stdin();
+    debugger.repl(stdin.lock(), io::stdout()).unwrap();
+}
@@ -0,0 +1,11 @@
+use std::env;
+use std::fs::File;
+use intcode::load_tape;
+use intcode::disasm::listing;
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| String::from("input.txt"));
+    let input_file = File::open(&path).unwrap_or_else(|_| panic!("failed to open {}", path));
+    let tape = load_tape(input_file);
+    print!("{}", listing(&tape));
+}
@@ -0,0 +1,36 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::process;
+use intcode::parse_tape;
+use intcode::save_binary_tape;
+use intcode::save_tape;
+use intcode::tape::decode_binary_tape;
+use intcode::tape::is_binary_tape;
+
+// usage: tape [--checksum] <input> <output>, converts a text tape to binary and a binary tape to text
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let checksum = args.first().map(String::as_str) == Some("--checksum");
+    if checksum {
+        args.remove(0);
+    }
+    if args.len() != 2 {
+        eprintln!("usage: tape [--checksum] <input> <output>");
+        process::exit(2);
+    }
+    let bytes = fs::read(&args[0]).unwrap_or_else(|error| panic!("failed to read {}: {}", args[0], error));
+    let binary = is_binary_tape(&bytes);
+    let tape = if binary {
+        decode_binary_tape(&bytes)
+    } else {
+        match String::from_utf8(bytes) {
+            Ok(text) => parse_tape(&text),
+            Err(_) => panic!("{} is neither a binary nor a text tape", args[0])
+        }
+    };
+    let tape: Vec<i64> = tape.unwrap_or_else(|error| panic!("{}: {}", args[0], error));
+    let output = File::create(&args[1]).unwrap_or_else(|error| panic!("failed to create {}: {}", args[1], error));
+    let result = if binary { save_tape(&tape, output) } else { save_binary_tape(&tape, output, checksum) };
+    result.unwrap_or_else(|error| panic!("failed to write {}: {}", args[1], error));
+}
@@ -0,0 +1,157 @@
+use crate::execute_fetched;
+use crate::Cell;
+use crate::fetch_instruction;
+use crate::Input;
+use crate::Instruction;
+use crate::IntcodeError;
+use crate::Memory;
+use crate::NoObserver;
+use crate::Observer;
+use crate::Opcode;
+use crate::Output;
+use crate::Storage;
+
+// code above this address is executed without caching, so jumps into sparse memory do not blow up the cache
+const CACHE_LIMIT: usize = 1 << 20;
+
+// Decoded instructions and their raw parameters by address. A write to a cell drops every entry whose instruction
+// covers that cell, so self-modifying programs see their changes on the next execution.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeCache<C: Cell = i64> {
+    entries: Vec<Option<(Instruction, [C; 3])>>
+}
+
+impl<C: Cell> DecodeCache<C> {
+    pub fn new() -> DecodeCache<C> {
+        DecodeCache { entries: Vec::new() }
+    }
+
+    // pre-decodes every cell of the tape; a cache built once can be cloned for any number of runs of the same tape
+    pub fn for_tape(tape: &[C]) -> DecodeCache<C> {
+        let memory = Memory::new(tape);
+        let entries = (0..tape.len().min(CACHE_LIMIT)).map(|address| fetch_instruction(&memory, address).ok()).collect();
+        DecodeCache { entries }
+    }
+
+    #[inline]
+    pub fn get(&self, address: usize) -> Option<&(Instruction, [C; 3])> {
+        self.entries.get(address).and_then(|entry| entry.as_ref())
+    }
+
+    pub fn insert(&mut self, address: usize, instruction: Instruction, params: [C; 3]) {
+        if address >= CACHE_LIMIT {
+            return;
+        }
+        if address >= self.entries.len() {
+            self.entries.resize(address + 1, None);
+        }
+        self.entries[address] = Some((instruction, params));
+    }
+
+    #[inline]
+    pub fn invalidate(&mut self, address: usize) {
+        let end = (address + 1).min(self.entries.len());
+        let start = address.saturating_sub(3).min(end);
+        for entry in &mut self.entries[start..end] {
+            *entry = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub fn try_execute_instruction_cached<S: Storage, I: Input<S::Cell>, O: Output<S::Cell>, B: Observer<S::Cell>>(memory: &mut Memory<S>, cache: &mut DecodeCache<S::Cell>, input: &mut I, output: &mut O, address: &mut usize, observer: &mut B) -> Result<bool, IntcodeError> {
+    let (instruction, params) = match cache.get(*address) {
+        Some(entry) => entry.clone(),
+        None => {
+            let (instruction, params) = fetch_instruction(memory, *address)?;
+            cache.insert(*address, instruction, params.clone());
+            (instruction, params)
+        }
+    };
+    let step = execute_fetched(memory, input, output, address, instruction, params, observer)?;
+    if let Some(write) = step.write {
+        cache.invalidate(write.address);
+    }
+    Ok(instruction.opcode != Opcode::Halt)
+}
+
+pub fn try_execute_intcode_cached_observed<C: Cell, I: Input<C>, O: Output<C>, B: Observer<C>>(memory: &[C], cache: &DecodeCache<C>, input: &mut I, output: &mut O, observer: &mut B) -> Result<Vec<C>, IntcodeError> {
+    let mut memory = Memory::new(memory);
+    let mut cache = cache.clone();
+
+    let mut address = 0;
+    while try_execute_instruction_cached(&mut memory, &mut cache, input, output, &mut address, observer)? {}
+
+    Ok(memory.into_cells())
+}
+
+pub fn try_execute_intcode_cached<C: Cell, I: Input<C>, O: Output<C>>(memory: &[C], cache: &DecodeCache<C>, input: &mut I, output: &mut O) -> Result<Vec<C>, IntcodeError> {
+    try_execute_intcode_cached_observed(memory, cache, input, output, &mut NoObserver)
+}
+
+pub fn execute_intcode_cached<C: Cell, I: Input<C>, O: Output<C>>(memory: &[C], cache: &DecodeCache<C>, input: &mut I, output: &mut O) -> Vec<C> {
+    match try_execute_intcode_cached(memory, cache, input, output) {
+        Ok(memory) => memory,
+        Err(error) => panic!("{}", error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::DecodeCache;
+    use crate::cache::execute_intcode_cached;
+    use crate::execute_intcode;
+    use crate::Machine;
+    use crate::Status;
+    use crate::StdInput;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    #[test]
+    fn same_results_as_interpreter() {
+        let programs = vec![
+            vec![1, 0, 0, 3, 99],
+            vec![1102, 5, 2, 3, 99],
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+            vec![109, 15, 109, 19, 204, -34, 99]
+        ];
+        for program in programs {
+            let mut expected = VecOutput::new();
+            let mut actual = VecOutput::new();
+            let memory = execute_intcode(&program, &mut StdInput, &mut expected);
+            let cache = DecodeCache::for_tape(&program);
+            assert_eq!(execute_intcode_cached(&program, &cache, &mut StdInput, &mut actual), memory);
+            assert_eq!(actual.values(), expected.values());
+        }
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        // the loop body increments the immediate parameter of its own output instruction
+        let tape = vec![104, 1, 1001, 1, 1, 1, 1007, 1, 5, 18, 1005, 18, 0, 99, 0, 0, 0, 0, 0];
+        let mut output = VecOutput::new();
+        execute_intcode_cached(&tape, &DecodeCache::for_tape(&tape), &mut VecInput::new(vec![]), &mut output);
+        assert_eq!(output.values(), &vec![1, 2, 3, 4]);
+
+        // rewriting the opcode itself
+        let tape = vec![1101, 0, 4, 7, 1105, 1, 7, 104, 9, 99];
+        let mut output = VecOutput::new();
+        let memory = execute_intcode_cached(&tape, &DecodeCache::for_tape(&tape), &mut StdInput, &mut output);
+        assert_eq!(memory, execute_intcode(&tape, &mut StdInput, &mut VecOutput::new()));
+        assert_eq!(output.values(), &vec![99]);
+    }
+
+    #[test]
+    fn machine_with_cache() {
+        let mut machine = Machine::with_decode_cache(&[3, 9, 1001, 9, 5, 10, 4, 10, 99, 0, 0]);
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        machine.memory_mut()[7] = 9;
+        machine.push_input(37);
+        assert_eq!(machine.run(), Ok(Status::Output(37)));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+use std::hash::Hash;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+// The value type of memory cells, inputs and outputs. Default must be zero. Addresses, opcodes and the relative base
+// stay machine integers, so cells only need to convert to i64 where they are used as one of those.
+pub trait Cell: Clone + Default + Eq + Ord + Hash + fmt::Debug + fmt::Display + FromStr {
+    fn from_i64(value: i64) -> Self;
+    fn to_i64(&self) -> Option<i64>;
+
+    // None reports an overflow, which the interpreter turns into IntcodeError::Overflow
+    fn try_add(&self, other: &Self) -> Option<Self>;
+    fn try_mul(&self, other: &Self) -> Option<Self>;
+
+    // for error reports, values that do not fit are clamped to the nearest i64
+    fn clamp_to_i64(&self) -> i64 {
+        match self.to_i64() {
+            Some(value) => value,
+            None if *self < Self::default() => i64::MIN,
+            None => i64::MAX
+        }
+    }
+}
+
+// native arithmetic, so overflow panics in debug builds and wraps around in release builds; see Checked
+impl Cell for i64 {
+    #[inline]
+    fn from_i64(value: i64) -> i64 {
+        value
+    }
+
+    #[inline]
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    #[inline]
+    fn try_add(&self, other: &i64) -> Option<i64> {
+        Some(self + other)
+    }
+
+    #[inline]
+    fn try_mul(&self, other: &i64) -> Option<i64> {
+        Some(self * other)
+    }
+}
+
+// native arithmetic like i64
+impl Cell for i128 {
+    fn from_i64(value: i64) -> i128 {
+        value as i128
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        if *self >= i64::MIN as i128 && *self <= i64::MAX as i128 { Some(*self as i64) } else { None }
+    }
+
+    fn try_add(&self, other: &i128) -> Option<i128> {
+        Some(self + other)
+    }
+
+    fn try_mul(&self, other: &i128) -> Option<i128> {
+        Some(self * other)
+    }
+}
+
+// an i64 that stops the machine with an error instead of overflowing
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checked(pub i64);
+
+impl fmt::Display for Checked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Checked {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Checked, ParseIntError> {
+        s.parse().map(Checked)
+    }
+}
+
+impl Cell for Checked {
+    fn from_i64(value: i64) -> Checked {
+        Checked(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    fn try_add(&self, other: &Checked) -> Option<Checked> {
+        self.0.checked_add(other.0).map(Checked)
+    }
+
+    fn try_mul(&self, other: &Checked) -> Option<Checked> {
+        self.0.checked_mul(other.0).map(Checked)
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use crate::disasm::decode_at;
+use crate::disasm::reachable_by_jumps;
+use crate::disasm::Entry;
+use crate::Instruction;
+use crate::Mode;
+use crate::Opcode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Jump(usize),
+    FallThrough(usize),
+    // the jump target comes from memory or the relative base and is only known at runtime
+    Unresolved
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Entry>,
+    pub edges: Vec<Edge>
+}
+
+impl Block {
+    // address right behind the last instruction of the block
+    pub fn end(&self) -> usize {
+        self.instructions.last().map_or(self.start, |entry| entry.address() + entry.len())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    blocks: BTreeMap<usize, Block>
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+    opcode == Opcode::JumpIfTrue || opcode == Opcode::JumpIfFalse
+}
+
+// like disasm::successors, but keeps jumps through memory as unresolved edges instead of dropping them
+fn edges(address: usize, instruction: &Instruction, params: &[i64], is_start: &[bool]) -> Vec<Edge> {
+    let next = address + instruction.len();
+    let fall_through = if next < is_start.len() && is_start[next] { vec![Edge::FallThrough(next)] } else { Vec::new() };
+    if instruction.opcode == Opcode::Halt {
+        return Vec::new();
+    }
+    if !is_jump(instruction.opcode) {
+        return fall_through;
+    }
+    let jump_on_nonzero = instruction.opcode == Opcode::JumpIfTrue;
+    let (always, never) = match instruction.modes[0] {
+        Mode::Immediate => ((params[0] != 0) == jump_on_nonzero, (params[0] != 0) != jump_on_nonzero),
+        _ => (false, false)
+    };
+    let mut result = Vec::new();
+    if !never {
+        match instruction.modes[1] {
+            Mode::Immediate if params[1] >= 0 => result.push(Edge::Jump(params[1] as usize)),
+            // jumping to a negative address always fails, so there is nothing to follow
+            Mode::Immediate => {},
+            _ => result.push(Edge::Unresolved)
+        }
+    }
+    if !always {
+        result.extend(fall_through);
+    }
+    result
+}
+
+impl ControlFlowGraph {
+    pub fn new(tape: &[i64]) -> ControlFlowGraph {
+        ControlFlowGraph::from_entry_points(tape, &[0])
+    }
+
+    // Splits all code reachable from the entry points into basic blocks. Only immediate jump targets are followed;
+    // code that is only entered through indirect jumps needs to be passed as an additional entry point.
+    pub fn from_entry_points(tape: &[i64], entry_points: &[usize]) -> ControlFlowGraph {
+        let is_start = reachable_by_jumps(tape, entry_points);
+        let mut leaders: BTreeSet<usize> = entry_points.iter().copied()
+            .filter(|&address| address < tape.len() && is_start[address]).collect();
+        for address in (0..tape.len()).filter(|&address| is_start[address]) {
+            let (instruction, params) = decode_at(tape, address).unwrap();
+            if is_jump(instruction.opcode) {
+                // a jump past the end of the tape or into data keeps its edge, but there is no block to start
+                for edge in edges(address, &instruction, &params, &is_start) {
+                    match edge {
+                        Edge::Jump(target) | Edge::FallThrough(target) if target < tape.len() && is_start[target] => {
+                            leaders.insert(target);
+                        },
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for &start in &leaders {
+            let mut instructions = Vec::new();
+            let mut address = start;
+            let block_edges = loop {
+                let (instruction, params) = decode_at(tape, address).unwrap();
+                let next = address + instruction.len();
+                let edges = edges(address, &instruction, &params, &is_start);
+                let ends_block = is_jump(instruction.opcode) || edges.is_empty() || leaders.contains(&next);
+                instructions.push(Entry::Code { address, instruction, params });
+                if ends_block {
+                    break edges;
+                }
+                address = next;
+            };
+            blocks.insert(start, Block { start, instructions, edges: block_edges });
+        }
+        ControlFlowGraph { blocks }
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    pub fn unresolved_count(&self) -> usize {
+        self.blocks().filter(|block| block.edges.contains(&Edge::Unresolved)).count()
+    }
+
+    // Graphviz output, e.g. `dot -Tsvg`. Unresolved edges point to a separate "?" node per block.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in self.blocks() {
+            let mut label = String::new();
+            for entry in &block.instructions {
+                write!(label, "{}\\l", entry).unwrap();
+            }
+            writeln!(out, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+            for edge in &block.edges {
+                match edge {
+                    Edge::Jump(target) => writeln!(out, "    b{} -> b{} [label=\"jump\"];", block.start, target),
+                    Edge::FallThrough(target) => writeln!(out, "    b{} -> b{};", block.start, target),
+                    Edge::Unresolved => {
+                        writeln!(out, "    u{} [label=\"?\", shape=circle];", block.start).unwrap();
+                        writeln!(out, "    b{} -> u{} [style=dashed];", block.start, block.start)
+                    }
+                }.unwrap();
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::cfg::ControlFlowGraph;
+    use crate::cfg::Edge;
+
+    #[test]
+    fn blocks_and_edges() {
+        let tape = vec![3, 9, 1005, 9, 8, 104, 0, 99, 4, 9, 99];
+        let graph = ControlFlowGraph::new(&tape);
+        let starts: Vec<usize> = graph.blocks().map(|block| block.start).collect();
+        assert_eq!(starts, vec![0, 5, 8]);
+        assert_eq!(graph.block(0).unwrap().instructions.len(), 2);
+        assert_eq!(graph.block(0).unwrap().end(), 5);
+        assert_eq!(graph.block(0).unwrap().edges, vec![Edge::Jump(8), Edge::FallThrough(5)]);
+        assert_eq!(graph.block(5).unwrap().edges, vec![]);
+        assert_eq!(graph.unresolved_count(), 0);
+    }
+
+    #[test]
+    fn unresolved_jumps() {
+        let source = "
+                    CALL #function
+                    HLT
+            function: OUT #1
+                    RET
+        ";
+        let tape = assemble(source).unwrap();
+        let graph = ControlFlowGraph::new(&tape);
+        // the return address is only pushed as a value, so the HLT after the call is not reached statically
+        assert!(graph.block(9).is_none());
+        assert_eq!(graph.block(10).unwrap().edges, vec![Edge::Unresolved]);
+        assert_eq!(graph.unresolved_count(), 1);
+
+        let graph = ControlFlowGraph::from_entry_points(&tape, &[0, 9]);
+        assert_eq!(graph.block(9).unwrap().instructions[0].to_string(), "0009: HLT");
+    }
+
+    #[test]
+    fn dot() {
+        let tape = vec![1006, 7, 6, 104, 1, 99, 2105, 1, 7, 0];
+        let dot = ControlFlowGraph::new(&tape).to_dot();
+        assert_eq!(dot, "digraph intcode {\n    node [shape=box, fontname=\"monospace\"];\n\
+            \x20   b0 [label=\"0000: JF [7], #6\\l\"];\n\
+            \x20   b0 -> b6 [label=\"jump\"];\n\
+            \x20   b0 -> b3;\n\
+            \x20   b3 [label=\"0003: OUT #1\\l0005: HLT\\l\"];\n\
+            \x20   b6 [label=\"0006: JT #1, [rb+7]\\l\"];\n\
+            \x20   u6 [label=\"?\", shape=circle];\n\
+            \x20   b6 -> u6 [style=dashed];\n}\n");
+    }
+
+    #[test]
+    fn jumps_out_of_code() {
+        // past the end of the tape and into a cell that does not decode
+        for (tape, target) in &[(vec![1105, 1, 100, 99], 100), (vec![1105, 1, 3, 0, 99], 3)] {
+            let graph = ControlFlowGraph::new(tape);
+            assert_eq!(graph.block(0).unwrap().edges, vec![Edge::Jump(*target)]);
+            assert_eq!(graph.blocks().count(), 1);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+use crate::disasm::disassemble_from;
+use crate::disasm::Entry;
+use crate::Cell;
+use crate::Memory;
+use crate::Observer;
+use crate::Step;
+use crate::Storage;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    // how often an instruction started at this address
+    pub executed: u64,
+    // operand reads and writes, fetching instructions does not count
+    pub reads: u64,
+    pub writes: u64,
+    // part of an instruction that was executed at least once
+    pub code: bool
+}
+
+impl Counts {
+    fn touched(&self) -> bool {
+        self.code || self.reads > 0 || self.writes > 0
+    }
+}
+
+// Records which cells a run executed, read and wrote. The size is usually the tape length; cells above it only show
+// up in reports if the program touched them.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    size: usize,
+    counts: HashMap<usize, Counts>
+}
+
+impl<C: Cell> Observer<C> for Coverage {
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, _memory: &Memory<S>) {
+        self.counts.entry(step.address).or_default().executed += 1;
+        for address in step.address..step.address + step.instruction.len() {
+            self.counts.entry(address).or_default().code = true;
+        }
+        let opcode = step.instruction.opcode;
+        for (i, operand) in step.operands().enumerate() {
+            if let Some(address) = operand.address {
+                let counts = self.counts.entry(address).or_default();
+                if opcode.writes() && i == opcode.param_count() - 1 {
+                    counts.writes += 1;
+                } else {
+                    counts.reads += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Coverage {
+    pub fn new(size: usize) -> Coverage {
+        Coverage { size, counts: HashMap::new() }
+    }
+
+    pub fn counts(&self, address: usize) -> Counts {
+        self.counts.get(&address).copied().unwrap_or_default()
+    }
+
+    // all touched addresses in ascending order
+    pub fn touched(&self) -> Vec<(usize, Counts)> {
+        let mut touched: Vec<(usize, Counts)> = self.counts.iter().map(|(&address, &counts)| (address, counts))
+            .filter(|(_, counts)| counts.touched()).collect();
+        touched.sort_unstable_by_key(|&(address, _)| address);
+        touched
+    }
+
+    // addresses where an instruction was executed, good entry points for the disassembler
+    pub fn entry_points(&self) -> Vec<usize> {
+        self.touched().into_iter().filter(|(_, counts)| counts.executed > 0).map(|(address, _)| address).collect()
+    }
+
+    // maximal ranges of cells below the size that were never executed, read or written
+    pub fn untouched(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for (address, _) in self.touched() {
+            if address >= self.size {
+                break;
+            }
+            if address > start {
+                ranges.push(start..address);
+            }
+            start = address + 1;
+        }
+        if start < self.size {
+            ranges.push(start..self.size);
+        }
+        ranges
+    }
+
+    pub fn to_text(&self) -> String {
+        let touched = self.touched();
+        let instructions = touched.iter().filter(|(_, counts)| counts.executed > 0).count();
+        let steps: u64 = touched.iter().map(|(_, counts)| counts.executed).sum();
+        let mut result = String::new();
+        writeln!(result, "{} instructions at {} addresses executed, {} of {} cells touched", steps, instructions,
+            touched.iter().filter(|&&(address, _)| address < self.size).count(), self.size).unwrap();
+        writeln!(result, "address  executed     reads    writes").unwrap();
+        for (address, counts) in &touched {
+            writeln!(result, "{:04}  {:>11} {:>9} {:>9}", address, counts.executed, counts.reads, counts.writes).unwrap();
+        }
+        writeln!(result, "untouched:").unwrap();
+        for range in self.untouched() {
+            writeln!(result, "{:04}-{:04}", range.start, range.end - 1).unwrap();
+        }
+        result
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("address,executed,reads,writes,code\n");
+        for (address, counts) in self.touched() {
+            writeln!(result, "{},{},{},{},{}", address, counts.executed, counts.reads, counts.writes, counts.code)
+                .unwrap();
+        }
+        result
+    }
+
+    pub fn to_json(&self) -> String {
+        let cells: Vec<String> = self.touched().into_iter().map(|(address, counts)| format!(
+            "{{\"address\":{},\"executed\":{},\"reads\":{},\"writes\":{},\"code\":{}}}",
+            address, counts.executed, counts.reads, counts.writes, counts.code)).collect();
+        let untouched: Vec<String> = self.untouched().into_iter()
+            .map(|range| format!("{{\"start\":{},\"end\":{}}}", range.start, range.end)).collect();
+        format!("{{\"size\":{},\"cells\":[{}],\"untouched\":[{}]}}\n", self.size, cells.join(","), untouched.join(","))
+    }
+
+    // the disassembly with every executed address as an entry point, each line prefixed with its execution count
+    pub fn listing(&self, tape: &[i64]) -> String {
+        let mut result = String::new();
+        for entry in disassemble_from(tape, &self.entry_points()) {
+            match &entry {
+                Entry::Code { address, .. } | Entry::Custom { address, .. } =>
+                    write!(result, "{:>9} ", self.counts(*address).executed).unwrap(),
+                Entry::Data { .. } => result.push_str("          ")
+            }
+            writeln!(result, "{}", entry).unwrap();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coverage::Counts;
+    use crate::coverage::Coverage;
+    use crate::try_execute_intcode_observed;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    // outputs the input unless it is zero, the cells after the halt are never touched
+    const TAPE: [i64; 12] = [3, 11, 1006, 11, 8, 4, 11, 99, 99, 0, 0, 0];
+
+    fn run(input: i64) -> Coverage {
+        let mut coverage = Coverage::new(TAPE.len());
+        try_execute_intcode_observed(&TAPE, &mut VecInput::new(vec![input]), &mut VecOutput::new(), &mut coverage)
+            .unwrap();
+        coverage
+    }
+
+    #[test]
+    fn counts() {
+        let coverage = run(5);
+        assert_eq!(coverage.entry_points(), vec![0, 2, 5, 7]);
+        assert_eq!(coverage.counts(11), Counts { executed: 0, reads: 2, writes: 1, code: false });
+        assert_eq!(coverage.counts(3), Counts { executed: 0, reads: 0, writes: 0, code: true });
+        assert_eq!(coverage.untouched(), vec![8..11]);
+
+        let coverage = run(0);
+        assert_eq!(coverage.entry_points(), vec![0, 2, 8]);
+        assert_eq!(coverage.untouched(), vec![5..8, 9..11]);
+    }
+
+    #[test]
+    fn reports() {
+        let coverage = run(0);
+        assert_eq!(coverage.to_csv(), "address,executed,reads,writes,code\n0,1,0,0,true\n1,0,0,0,true\n\
+            2,1,0,0,true\n3,0,0,0,true\n4,0,0,0,true\n8,1,0,0,true\n11,0,1,1,false\n");
+        assert_eq!(coverage.to_json(), "{\"size\":12,\"cells\":[\
+            {\"address\":0,\"executed\":1,\"reads\":0,\"writes\":0,\"code\":true},\
+            {\"address\":1,\"executed\":0,\"reads\":0,\"writes\":0,\"code\":true},\
+            {\"address\":2,\"executed\":1,\"reads\":0,\"writes\":0,\"code\":true},\
+            {\"address\":3,\"executed\":0,\"reads\":0,\"writes\":0,\"code\":true},\
+            {\"address\":4,\"executed\":0,\"reads\":0,\"writes\":0,\"code\":true},\
+            {\"address\":8,\"executed\":1,\"reads\":0,\"writes\":0,\"code\":true},\
+            {\"address\":11,\"executed\":0,\"reads\":1,\"writes\":1,\"code\":false}],\
+            \"untouched\":[{\"start\":5,\"end\":8},{\"start\":9,\"end\":11}]}\n");
+        assert!(coverage.to_text().starts_with("3 instructions at 3 addresses executed, 7 of 12 cells touched\n"));
+        assert!(coverage.to_text().ends_with("untouched:\n0005-0007\n0009-0010\n"));
+        // the skipped output is still listed as code because the jump can reach it
+        let listing = coverage.listing(&TAPE);
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(&lines[2..6], &["        0 0005: OUT [11]", "        0 0007: HLT", "        1 0008: HLT",
+            "          0009: DATA 0"]);
+    }
+}
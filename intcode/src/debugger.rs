@@ -0,0 +1,369 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use crate::disasm::format_custom;
+use crate::disasm::format_instruction;
+use crate::history::UndoLog;
+use crate::isa::Progress;
+use crate::try_execute_instruction_observed;
+use crate::Input;
+use crate::Instruction;
+use crate::InstructionSet;
+use crate::IntcodeError;
+use crate::Memory;
+use crate::Opcode;
+use crate::Output;
+use crate::Snapshot;
+
+// how many instructions step_back can take back unless set otherwise
+const DEFAULT_HISTORY: usize = 1 << 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Stepped,
+    Breakpoint(usize),
+    OpcodeBreakpoint(Opcode),
+    Halted,
+    Error(IntcodeError)
+}
+
+pub struct Debugger<I: Input, O: Output> {
+    memory: Memory,
+    address: usize,
+    input: I,
+    output: O,
+    breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: Vec<Opcode>,
+    history: UndoLog,
+    instructions: InstructionSet,
+    halted: bool
+}
+
+impl<I: Input, O: Output> Debugger<I, O> {
+    pub fn new(tape: &[i64], input: I, output: O) -> Debugger<I, O> {
+        Debugger { memory: Memory::new(tape), address: 0, input, output, breakpoints: BTreeSet::new(),
+            opcode_breakpoints: Vec::new(), history: UndoLog::new(DEFAULT_HISTORY),
+            instructions: InstructionSet::standard(), halted: false }
+    }
+
+    // custom instructions can not be stepped back over, executing one clears the history
+    pub fn set_instruction_set(&mut self, instructions: InstructionSet) {
+        self.instructions = instructions;
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn add_opcode_breakpoint(&mut self, opcode: Opcode) {
+        if !self.opcode_breakpoints.contains(&opcode) {
+            self.opcode_breakpoints.push(opcode);
+        }
+    }
+
+    pub fn remove_opcode_breakpoint(&mut self, opcode: Opcode) -> bool {
+        let count = self.opcode_breakpoints.len();
+        self.opcode_breakpoints.retain(|&o| o != opcode);
+        count != self.opcode_breakpoints.len()
+    }
+
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    // changing the state by hand clears the history, stepping back over it would mix the two states
+    pub fn set_address(&mut self, address: usize) {
+        self.address = address;
+        self.halted = false;
+        self.history.clear();
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.memory.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.memory.relative_base = relative_base;
+        self.history.clear();
+    }
+
+    pub fn peek(&self, address: usize) -> i64 {
+        self.memory[address]
+    }
+
+    // false if the address is beyond what the memory can hold
+    pub fn poke(&mut self, address: usize, value: i64) -> bool {
+        if !self.memory.write(address, value) {
+            return false;
+        }
+        self.history.clear();
+        true
+    }
+
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history.set_capacity(capacity);
+    }
+
+    // Takes back the newest instruction. Inputs and outputs stay consumed and produced, stepping forward over an
+    // input instruction again reads the next value.
+    pub fn step_back(&mut self) -> bool {
+        match self.history.undo(&mut self.memory, &mut self.address) {
+            Some(_) => {
+                self.halted = false;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    // pending values of the Input implementation are not part of the snapshot, and the debugger counts no steps
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { memory: self.memory.cells().to_vec(), address: self.address, relative_base: self.memory.relative_base,
+            ..Snapshot::default() }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = Memory::new(&snapshot.memory);
+        self.memory.relative_base = snapshot.relative_base;
+        self.set_address(snapshot.address);
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn current_instruction(&self) -> String {
+        let value = self.memory[self.address];
+        let params = |count: usize| -> Vec<i64> { (1..=count).map(|i| self.memory[self.address + i]).collect() };
+        let text = match Instruction::decode(value) {
+            Ok(instruction) if self.instructions.builtin(&value).is_some() =>
+                Some(format_instruction(&instruction, &params(instruction.len() - 1))),
+            _ => self.instructions.get(value % 100).and_then(|definition| {
+                let modes = definition.modes(value).ok()?;
+                Some(format_custom(definition.mnemonic, &modes, &params(definition.arity), &definition.writes))
+            })
+        };
+        match text {
+            Some(text) => format!("{:04}: {}", self.address, text),
+            None => format!("{:04}: DATA {}", self.address, value)
+        }
+    }
+
+    pub fn step(&mut self) -> StopReason {
+        if self.halted {
+            return StopReason::Halted;
+        }
+        let result = if self.instructions.builtin(&self.memory[self.address]).is_some() {
+            try_execute_instruction_observed(&mut self.memory, &mut self.input, &mut self.output, &mut self.address,
+                &mut self.history).map(|running| if running { Progress::Running } else { Progress::Halted(None) })
+        } else {
+            self.history.clear();
+            self.instructions.execute_custom(&mut self.memory, &mut self.input, &mut self.output, &mut self.address,
+                &mut Vec::new())
+        };
+        match result {
+            Ok(Progress::Running) => StopReason::Stepped,
+            Ok(Progress::Halted(_)) => {
+                self.halted = true;
+                StopReason::Halted
+            },
+            Err(error) => StopReason::Error(error)
+        }
+    }
+
+    // always executes at least one instruction, so continuing from a breakpoint does not stop right away
+    pub fn cont(&mut self) -> StopReason {
+        loop {
+            match self.step() {
+                StopReason::Stepped => {},
+                reason => return reason
+            }
+            if self.breakpoints.contains(&self.address) {
+                return StopReason::Breakpoint(self.address);
+            }
+            if let Ok(instruction) = Instruction::decode(self.memory[self.address]) {
+                if self.opcode_breakpoints.contains(&instruction.opcode) {
+                    return StopReason::OpcodeBreakpoint(instruction.opcode);
+                }
+            }
+        }
+    }
+
+    fn execute_command<W: Write>(&mut self, command: &str, args: &[&str], out: &mut W) -> io::Result<bool> {
+        let number = |i: usize| args.get(i).and_then(|arg| arg.parse::<i64>().ok());
+        match (command, args.len()) {
+            ("s", _) | ("step", _) => {
+                let mut reason = StopReason::Stepped;
+                for _ in 0..number(0).unwrap_or(1) {
+                    reason = self.step();
+                    if reason != StopReason::Stepped {
+                        break;
+                    }
+                }
+                writeln!(out, "{:?}", reason)?;
+                writeln!(out, "{}", self.current_instruction())?;
+            },
+            ("back", _) | ("rs", _) => {
+                let count = number(0).unwrap_or(1);
+                let undone = (0..count).take_while(|_| self.step_back()).count();
+                writeln!(out, "stepped back {} instruction{}", undone, if undone == 1 { "" } else { "s" })?;
+                writeln!(out, "{}", self.current_instruction())?;
+            },
+            ("c", 0) | ("continue", 0) => {
+                writeln!(out, "{:?}", self.cont())?;
+                writeln!(out, "{}", self.current_instruction())?;
+            },
+            ("b", 1) | ("break", 1) | ("d", 1) | ("delete", 1) => {
+                let add = command.starts_with('b');
+                match (number(0), Opcode::from_mnemonic(args[0])) {
+                    (Some(address), _) if address >= 0 && add => self.add_breakpoint(address as usize),
+                    (Some(address), _) if address >= 0 => { self.remove_breakpoint(address as usize); },
+                    (_, Some(opcode)) if add => self.add_opcode_breakpoint(opcode),
+                    (_, Some(opcode)) => { self.remove_opcode_breakpoint(opcode); },
+                    _ => writeln!(out, "expected an address or a mnemonic")?
+                }
+            },
+            ("bl", 0) => {
+                for address in &self.breakpoints {
+                    writeln!(out, "{:04}", address)?;
+                }
+                for opcode in &self.opcode_breakpoints {
+                    writeln!(out, "{}", opcode.mnemonic())?;
+                }
+            },
+            ("x", 1) | ("x", 2) => match number(0) {
+                Some(start) if start >= 0 => {
+                    for address in start as usize..start as usize + number(1).unwrap_or(1).max(0) as usize {
+                        writeln!(out, "{:04}: {}", address, self.peek(address))?;
+                    }
+                },
+                _ => writeln!(out, "expected an address")?
+            },
+            ("poke", 2) => match (number(0), number(1)) {
+                (Some(address), Some(value)) if address >= 0 => if !self.poke(address as usize, value) {
+                    writeln!(out, "address {} is out of range", address)?
+                },
+                _ => writeln!(out, "expected an address and a value")?
+            },
+            ("rb", 0) => writeln!(out, "{}", self.relative_base())?,
+            ("rb", 1) => match number(0) {
+                Some(value) => self.set_relative_base(value),
+                None => writeln!(out, "expected a value")?
+            },
+            ("jump", 1) => match number(0) {
+                Some(address) if address >= 0 => self.set_address(address as usize),
+                _ => writeln!(out, "expected an address")?
+            },
+            ("i", 0) | ("info", 0) => {
+                writeln!(out, "address: {}, relative base: {}", self.address, self.relative_base())?;
+                writeln!(out, "{}", self.current_instruction())?;
+            },
+            ("q", 0) | ("quit", 0) => return Ok(false),
+            _ => writeln!(out, "commands: s/step [n], rs/back [n], c/continue, b/break <address|mnemonic>, \
+                d/delete <address|mnemonic>, bl, x <address> [count], poke <address> <value>, rb [value], \
+                jump <address>, i/info, q/quit")?
+        }
+        Ok(true)
+    }
+
+    // reads one command per line until "quit" or the end of the command stream
+    pub fn repl<R: BufRead, W: Write>(&mut self, commands: R, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", self.current_instruction())?;
+        for line in commands.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => continue
+            };
+            let args: Vec<&str> = words.collect();
+            if !self.execute_command(command, &args, &mut out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::Debugger;
+    use crate::debugger::StopReason;
+    use crate::Opcode;
+    use crate::StdInput;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    #[test]
+    fn breakpoints() {
+        let tape = vec![3, 9, 1001, 9, 5, 9, 4, 9, 99, 0];
+        let mut debugger = Debugger::new(&tape, VecInput::new(vec![37]), VecOutput::new());
+        debugger.add_breakpoint(6);
+        assert_eq!(debugger.current_instruction(), "0000: IN -> [9]");
+        assert_eq!(debugger.cont(), StopReason::Breakpoint(6));
+        assert_eq!(debugger.peek(9), 42);
+        let snapshot = debugger.snapshot();
+        assert!(debugger.poke(9, 7));
+        assert_eq!(debugger.step(), StopReason::Stepped);
+        assert_eq!(debugger.output_mut().values(), &vec![7]);
+        assert_eq!(debugger.cont(), StopReason::Halted);
+        assert_eq!(debugger.step(), StopReason::Halted);
+        debugger.restore(&snapshot);
+        assert_eq!(debugger.cont(), StopReason::Halted);
+        assert_eq!(debugger.output_mut().values(), &vec![7, 42]);
+    }
+
+    #[test]
+    fn opcode_breakpoints() {
+        let tape = vec![109, 19, 204, -19, 109, 1, 99];
+        let mut debugger = Debugger::new(&tape, StdInput, VecOutput::new());
+        debugger.add_opcode_breakpoint(Opcode::AdjustRelativeBase);
+        assert_eq!(debugger.cont(), StopReason::OpcodeBreakpoint(Opcode::AdjustRelativeBase));
+        assert_eq!(debugger.address(), 4);
+        assert_eq!(debugger.relative_base(), 19);
+        assert_eq!(debugger.output_mut().values(), &vec![109]);
+    }
+
+    #[test]
+    fn repl() {
+        let tape = vec![1101, 1, 2, 7, 4, 7, 99, 0];
+        let mut debugger = Debugger::new(&tape, StdInput, VecOutput::new());
+        let commands = "b 4\nc\nx 7\npoke 7 10\npoke 99999999 1\nrb 5\ni\nc\nq\ns\n";
+        let mut out = Vec::new();
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0000: ADD #1, #2 -> [7]\nBreakpoint(4)\n0004: OUT [7]\n\
+            0007: 3\naddress 99999999 is out of range\naddress: 4, relative base: 5\n0004: OUT [7]\nHalted\n0006: HLT\n");
+        assert_eq!(debugger.output_mut().values(), &vec![10]);
+    }
+
+    #[test]
+    fn step_back() {
+        let tape = vec![3, 9, 1001, 9, 5, 9, 4, 9, 99, 0];
+        let mut debugger = Debugger::new(&tape, VecInput::new(vec![37, 38]), VecOutput::new());
+        assert_eq!(debugger.cont(), StopReason::Halted);
+        let commands = "back 2\nx 9\nrs 9\nx 9\ns 2\nx 9\n";
+        let mut out = Vec::new();
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0008: HLT\nstepped back 2 instructions\n0006: OUT [9]\n\
+            0009: 42\nstepped back 2 instructions\n0000: IN -> [9]\n0009: 0\nStepped\n0006: OUT [9]\n0009: 43\n");
+        // the input was consumed again, the output of the first run stays
+        assert_eq!(debugger.output_mut().values(), &vec![42]);
+    }
+}
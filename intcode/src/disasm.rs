@@ -0,0 +1,289 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use crate::isa::Definition;
+use crate::isa::Semantics;
+use crate::Instruction;
+use crate::InstructionSet;
+use crate::Mode;
+use crate::Opcode;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Entry {
+    Code { address: usize, instruction: Instruction, params: Vec<i64> },
+    // an instruction of an instruction set that is not one of the standard opcodes
+    Custom { address: usize, mnemonic: &'static str, modes: Vec<Mode>, params: Vec<i64>, writes: Vec<usize> },
+    Data { address: usize, value: i64 }
+}
+
+impl Entry {
+    pub fn address(&self) -> usize {
+        match self {
+            Entry::Code { address, .. } => *address,
+            Entry::Custom { address, .. } => *address,
+            Entry::Data { address, .. } => *address
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Entry::Code { instruction, .. } => instruction.len(),
+            Entry::Custom { params, .. } => params.len() + 1,
+            Entry::Data { .. } => 1
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Entry::Code { address, instruction, params } =>
+                write!(f, "{:04}: {}", address, format_instruction(instruction, params)),
+            Entry::Custom { address, mnemonic, modes, params, writes } =>
+                write!(f, "{:04}: {}", address, format_custom(mnemonic, modes, params, writes)),
+            Entry::Data { address, value } => write!(f, "{:04}: DATA {}", address, value)
+        }
+    }
+}
+
+pub fn format_param(mode: Mode, value: i64) -> String {
+    match mode {
+        Mode::Position => format!("[{}]", value),
+        Mode::Immediate => format!("#{}", value),
+        Mode::Relative if value < 0 => format!("[rb{}]", value),
+        Mode::Relative => format!("[rb+{}]", value)
+    }
+}
+
+// renders an instruction without its address, e.g. "ADD [rb-3], #5 -> [100]"
+pub fn format_instruction(instruction: &Instruction, params: &[i64]) -> String {
+    let count = instruction.opcode.param_count();
+    let writes = if instruction.opcode.writes() { vec![count - 1] } else { Vec::new() };
+    format_custom(instruction.opcode.mnemonic(), &instruction.modes[..count], params, &writes)
+}
+
+// Like format_instruction for any definition. Only a written last operand gets an arrow, so that the assembler
+// can read the line back.
+pub fn format_custom(mnemonic: &str, modes: &[Mode], params: &[i64], writes: &[usize]) -> String {
+    let mut result = mnemonic.to_string();
+    let count = modes.len();
+    for (i, (mode, value)) in modes.iter().zip(params).enumerate() {
+        if writes.contains(&i) && i == count - 1 {
+            result.push_str(" -> ");
+        } else if i == 0 {
+            result.push(' ');
+        } else {
+            result.push_str(", ");
+        }
+        result.push_str(&format_param(*mode, *value));
+    }
+    result
+}
+
+// only accepts instructions that re-encode to exactly the same cell and fit into the tape, so that the listing
+// does not lose any information
+pub fn decode_at(tape: &[i64], address: usize) -> Option<(Instruction, Vec<i64>)> {
+    let value = *tape.get(address)?;
+    let instruction = Instruction::decode(value).ok()?;
+    if instruction.encode() != value || address + instruction.len() > tape.len() {
+        return None;
+    }
+    Some((instruction, tape[address + 1..address + instruction.len()].to_vec()))
+}
+
+// decodes standard opcodes the set runs on the built-in interpreter with decode_at and custom ones from their
+// definition, with the same restrictions
+pub fn decode_with(tape: &[i64], address: usize, set: &InstructionSet) -> Option<Entry> {
+    let value = *tape.get(address)?;
+    if set.builtin(&value).is_some() {
+        let (instruction, params) = decode_at(tape, address)?;
+        return Some(Entry::Code { address, instruction, params });
+    }
+    let definition: &Definition = set.get(value % 100)?;
+    if let Semantics::Builtin(_) = definition.semantics {
+        return None;
+    }
+    let modes = definition.modes(value).ok()?;
+    if definition.encode(&modes) != value || address + definition.arity + 1 > tape.len() {
+        return None;
+    }
+    Some(Entry::Custom { address, mnemonic: definition.mnemonic, modes,
+        params: tape[address + 1..address + 1 + definition.arity].to_vec(), writes: definition.writes.clone() })
+}
+
+// statically known successors of an instruction; jumps through memory or the relative base can not be followed
+pub fn successors(address: usize, instruction: &Instruction, params: &[i64]) -> Vec<usize> {
+    let next = address + instruction.len();
+    match instruction.opcode {
+        Opcode::Halt => Vec::new(),
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let jump_on_nonzero = instruction.opcode == Opcode::JumpIfTrue;
+            let (always, never) = match instruction.modes[0] {
+                Mode::Immediate => ((params[0] != 0) == jump_on_nonzero, (params[0] != 0) != jump_on_nonzero),
+                _ => (false, false)
+            };
+            let mut result = Vec::new();
+            if !never && instruction.modes[1] == Mode::Immediate && params[1] >= 0 {
+                result.push(params[1] as usize);
+            }
+            if !always {
+                result.push(next);
+            }
+            result
+        },
+        _ => vec![next]
+    }
+}
+
+// custom instructions are assumed to continue with the next instruction
+fn visit(tape: &[i64], is_start: &mut [bool], mut to_visit: Vec<usize>, set: &InstructionSet) {
+    while let Some(address) = to_visit.pop() {
+        if address >= tape.len() || is_start[address] {
+            continue;
+        }
+        match decode_with(tape, address, set) {
+            Some(Entry::Code { instruction, params, .. }) =>
+                to_visit.extend(successors(address, &instruction, &params)),
+            Some(entry) => to_visit.push(address + entry.len()),
+            None => continue
+        }
+        is_start[address] = true;
+    }
+}
+
+// only follows immediate jump targets, see reachable for code that is entered by returning from a function
+pub fn reachable_by_jumps(tape: &[i64], entry_points: &[usize]) -> Vec<bool> {
+    let mut is_start = vec![false; tape.len()];
+    visit(tape, &mut is_start, entry_points.to_vec(), &InstructionSet::standard());
+    is_start
+}
+
+// Immediate values of known instructions that point right behind a jump, which is how programs push return
+// addresses before calling a function. Only returns addresses that are not known yet and decode as an instruction.
+pub fn return_sites(tape: &[i64], is_start: &[bool]) -> Vec<usize> {
+    let mut constants = BTreeSet::new();
+    let mut after_jumps = BTreeSet::new();
+    for address in (0..tape.len()).filter(|&address| is_start[address]) {
+        let (instruction, params) = match decode_at(tape, address) {
+            Some(decoded) => decoded,
+            None => continue
+        };
+        let count = instruction.opcode.param_count() - if instruction.opcode.writes() { 1 } else { 0 };
+        for (mode, value) in instruction.modes.iter().zip(&params).take(count) {
+            if *mode == Mode::Immediate && *value >= 0 {
+                constants.insert(*value as usize);
+            }
+        }
+        if instruction.opcode == Opcode::JumpIfTrue || instruction.opcode == Opcode::JumpIfFalse {
+            after_jumps.insert(address + instruction.len());
+        }
+    }
+    constants.intersection(&after_jumps)
+        .filter(|&&address| address < tape.len() && !is_start[address] && decode_at(tape, address).is_some())
+        .copied().collect()
+}
+
+// follows immediate jump targets as well as return addresses
+pub fn reachable(tape: &[i64], entry_points: &[usize]) -> Vec<bool> {
+    reachable_with(tape, entry_points, &InstructionSet::standard())
+}
+
+pub fn reachable_with(tape: &[i64], entry_points: &[usize], set: &InstructionSet) -> Vec<bool> {
+    let mut is_start = vec![false; tape.len()];
+    let mut to_visit = entry_points.to_vec();
+    loop {
+        visit(tape, &mut is_start, to_visit, set);
+        to_visit = return_sites(tape, &is_start);
+        if to_visit.is_empty() {
+            return is_start;
+        }
+    }
+}
+
+pub fn disassemble_from(tape: &[i64], entry_points: &[usize]) -> Vec<Entry> {
+    disassemble_with(tape, entry_points, &InstructionSet::standard())
+}
+
+pub fn disassemble_with(tape: &[i64], entry_points: &[usize], set: &InstructionSet) -> Vec<Entry> {
+    let is_start = reachable_with(tape, entry_points, set);
+    let mut entries = Vec::new();
+    let mut address = 0;
+    while address < tape.len() {
+        let entry = match decode_with(tape, address, set) {
+            Some(entry) if is_start[address] => entry,
+            _ => Entry::Data { address, value: tape[address] }
+        };
+        address += entry.len();
+        entries.push(entry);
+    }
+    entries
+}
+
+pub fn disassemble(tape: &[i64]) -> Vec<Entry> {
+    disassemble_from(tape, &[0])
+}
+
+pub fn listing(tape: &[i64]) -> String {
+    listing_with(tape, &InstructionSet::standard())
+}
+
+pub fn listing_with(tape: &[i64], set: &InstructionSet) -> String {
+    let mut result = String::new();
+    for entry in disassemble_with(tape, &[0], set) {
+        result.push_str(&entry.to_string());
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disasm::listing;
+    use crate::disasm::disassemble;
+    use crate::disasm::reachable_by_jumps;
+    use crate::disasm::Entry;
+
+    #[test]
+    fn modes() {
+        let tape = vec![109, 7, 21101, -3, 5, 100, 99, 0];
+        assert_eq!(listing(&tape), "0000: ARB #7\n0002: ADD #-3, #5 -> [rb+100]\n0006: HLT\n0007: DATA 0\n");
+        let tape = vec![1201, -3, 5, 100, 99];
+        assert_eq!(listing(&tape), "0000: ADD [rb-3], #5 -> [100]\n0004: HLT\n");
+    }
+
+    #[test]
+    fn io_and_jumps() {
+        let tape = vec![3, 9, 1005, 9, 8, 104, 0, 99, 4, 9, 99];
+        assert_eq!(listing(&tape), "0000: IN -> [9]\n0002: JT [9], #8\n0005: OUT #0\n0007: HLT\n\
+            0008: OUT [9]\n0010: HLT\n");
+    }
+
+    #[test]
+    fn unreachable_cells_are_data() {
+        // the unconditional jump skips over cells that would decode as instructions
+        let tape = vec![1105, 1, 5, 1, 99, 99];
+        let entries = disassemble(&tape);
+        assert_eq!(entries[1], Entry::Data { address: 3, value: 1 });
+        assert_eq!(entries[2], Entry::Data { address: 4, value: 99 });
+        assert_eq!(entries[3].to_string(), "0005: HLT");
+    }
+
+    #[test]
+    fn non_canonical_cells_are_data() {
+        // an unused mode digit and a truncated instruction can not be listed without losing information
+        let tape = vec![1104, 3, 1, 0];
+        assert_eq!(listing(&tape), "0000: DATA 1104\n0001: DATA 3\n0002: DATA 1\n0003: DATA 0\n");
+    }
+
+    #[test]
+    fn return_addresses() {
+        // pushes 7 as the return address, calls the function at 10, which returns through [rb+0]
+        let tape = vec![21101, 7, 0, 0, 1105, 1, 10, 104, 42, 99, 2106, 0, 0];
+        assert_eq!(listing(&tape), "0000: ADD #7, #0 -> [rb+0]\n0004: JT #1, #10\n0007: OUT #42\n0009: HLT\n\
+            0010: JF #0, [rb+0]\n");
+        assert!(!reachable_by_jumps(&tape, &[0])[7]);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use crate::Cell;
+use crate::Instruction;
+use crate::Memory;
+use crate::MemoryWrite;
+use crate::Observer;
+use crate::Opcode;
+use crate::Step;
+use crate::Storage;
+
+// what one executed instruction changed, including the cells a device changed right before it; only adjustments
+// store the old relative base
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Change<C: Cell> {
+    address: usize,
+    opcode: Opcode,
+    relative_base: Option<i64>,
+    write: Option<(usize, C)>,
+    external: Vec<(usize, C)>
+}
+
+// an instruction that was taken back, with the write it made and the device writes before it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Undone<C: Cell = i64> {
+    pub address: usize,
+    pub opcode: Opcode,
+    pub write: Option<MemoryWrite<C>>,
+    pub external: Vec<MemoryWrite<C>>
+}
+
+// Records the previous instruction pointer, relative base and overwritten cell of every instruction, so a paused
+// machine can be stepped backwards. Only the newest capacity instructions are kept. Undoing an instruction does
+// not take back its input or output, that is up to the owner of the I/O.
+#[derive(Clone, Debug)]
+pub struct UndoLog<C: Cell = i64> {
+    changes: VecDeque<Change<C>>,
+    capacity: usize,
+    relative_base: i64,
+    external: Vec<(usize, C)>
+}
+
+impl<C: Cell> UndoLog<C> {
+    pub fn new(capacity: usize) -> UndoLog<C> {
+        UndoLog { changes: VecDeque::new(), capacity, relative_base: 0, external: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // drops the oldest changes if there are more than the new capacity
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.changes.len() > capacity {
+            self.changes.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.external.clear();
+    }
+
+    // restores the state before the newest recorded instruction and moves the instruction pointer back to it
+    pub fn undo<S: Storage<Cell = C>>(&mut self, memory: &mut Memory<S>, address: &mut usize) -> Option<Undone<C>> {
+        let change = self.changes.pop_back()?;
+        let write = change.write.map(|(target, old)| {
+            let new = std::mem::replace(&mut memory[target], old.clone());
+            MemoryWrite { address: target, old, new }
+        });
+        let external = change.external.into_iter().rev().map(|(target, old)| {
+            let new = std::mem::replace(&mut memory[target], old.clone());
+            MemoryWrite { address: target, old, new }
+        }).collect();
+        if let Some(relative_base) = change.relative_base {
+            memory.relative_base = relative_base;
+        }
+        *address = change.address;
+        Some(Undone { address: change.address, opcode: change.opcode, write, external })
+    }
+}
+
+impl<C: Cell> Observer<C> for UndoLog<C> {
+    fn before_instruction<S: Storage<Cell = C>>(&mut self, _address: usize, _instruction: &Instruction, memory: &Memory<S>) {
+        self.relative_base = memory.relative_base;
+    }
+
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, _memory: &Memory<S>) {
+        let external = std::mem::take(&mut self.external);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.changes.len() == self.capacity {
+            self.changes.pop_front();
+        }
+        let relative_base = if step.relative_base != self.relative_base { Some(self.relative_base) } else { None };
+        let write = step.write.as_ref().map(|write| (write.address, write.old.clone()));
+        self.changes.push_back(Change { address: step.address, opcode: step.instruction.opcode, relative_base, write,
+            external });
+    }
+
+    fn external_write<S: Storage<Cell = C>>(&mut self, write: &MemoryWrite<C>, _memory: &Memory<S>) {
+        self.external.push((write.address, write.old.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::UndoLog;
+    use crate::try_execute_instruction_observed;
+    use crate::Machine;
+    use crate::Memory;
+    use crate::Opcode;
+    use crate::Status;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    #[test]
+    fn step_back() {
+        let tape = vec![109, 5, 21101, 2, 3, 0, 1001, 5, 1, 5, 99];
+        let mut memory = Memory::new(&tape);
+        let mut address = 0;
+        let mut log = UndoLog::new(2);
+        let mut states = vec![(memory.clone(), address)];
+        while try_execute_instruction_observed(&mut memory, &mut VecInput::new(vec![]), &mut VecOutput::new(),
+            &mut address, &mut log).unwrap() {
+            states.push((memory.clone(), address));
+        }
+        assert_eq!(log.len(), 2);
+
+        // the halt and the last addition are in the log, the adjustment and the first addition were dropped
+        assert_eq!(log.undo(&mut memory, &mut address).map(|undone| undone.opcode), Some(Opcode::Halt));
+        assert_eq!((&memory, address), (&states[3].0, states[3].1));
+        let undone = log.undo(&mut memory, &mut address).unwrap();
+        assert_eq!((undone.opcode, undone.write.map(|write| (write.old, write.new))), (Opcode::Add, Some((5, 6))));
+        assert_eq!((&memory, address), (&states[2].0, states[2].1));
+        assert_eq!(log.undo(&mut memory, &mut address), None);
+
+        let mut memory = Memory::new(&tape);
+        let mut address = 0;
+        log.set_capacity(10);
+        for _ in 0..2 {
+            try_execute_instruction_observed(&mut memory, &mut VecInput::new(vec![]), &mut VecOutput::new(),
+                &mut address, &mut log).unwrap();
+        }
+        log.undo(&mut memory, &mut address);
+        log.undo(&mut memory, &mut address);
+        assert_eq!((&memory, address), (&states[0].0, states[0].1));
+    }
+
+    #[test]
+    fn machine_step_back() {
+        // outputs every input plus one
+        let tape = vec![3, 9, 1001, 9, 1, 9, 4, 9, 1105, 1, 0];
+        let mut machine = Machine::with_decode_cache(&tape);
+        machine.set_history_capacity(100);
+        machine.extend_input(&[1, 2]);
+        assert_eq!(machine.run(), Ok(Status::Output(2)));
+        assert_eq!(machine.run(), Ok(Status::Output(3)));
+        for _ in 0..4 {
+            assert!(machine.step_back());
+        }
+        assert_eq!((machine.address(), machine.steps()), (8, 3));
+        assert_eq!((machine.pending_inputs(), machine.memory()[9]), (1, 2));
+        machine.memory_mut()[9] = 10;
+        assert!(!machine.step_back());
+        assert_eq!(machine.run(), Ok(Status::Output(3)));
+    }
+}
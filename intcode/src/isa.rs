@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+use crate::cell_address;
+use crate::fault_state;
+use crate::fetch_instruction;
+use crate::read_operand;
+use crate::relative_address;
+use crate::try_execute_instruction as try_execute_builtin;
+use crate::Cell;
+use crate::FaultState;
+use crate::Input;
+use crate::IntcodeError;
+use crate::Memory;
+use crate::MemoryWrite;
+use crate::Mode;
+use crate::Opcode;
+use crate::Output;
+use crate::Storage;
+
+// what the interpreter does after an instruction's semantics ran
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Effect<C: Cell = i64> {
+    Continue,
+    Jump(usize),
+    // an optional exit code, e.g. for a halt-with-code instruction
+    Halt(Option<C>)
+}
+
+// whether the machine can go on after an instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Progress<C: Cell = i64> {
+    Running,
+    // the exit code of the halting instruction, standard halts have none
+    Halted(Option<C>)
+}
+
+// Everything an instruction can see and change while it executes. Operands are already resolved: for read
+// operands value() is the value the mode selects, for write operands it is the current content of the target cell.
+pub struct Context<'a, C: Cell> {
+    address: usize,
+    operands: &'a [(Option<usize>, C)],
+    storage: &'a mut dyn Storage<Cell = C>,
+    relative_base: &'a mut i64,
+    input: &'a mut dyn Input<C>,
+    output: &'a mut dyn Output<C>,
+    writes: &'a mut Vec<MemoryWrite<C>>
+}
+
+impl<'a, C: Cell> Context<'a, C> {
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    pub fn fault_state(&self) -> FaultState {
+        let instruction = self.storage.cell(self.address).map_or(0, Cell::clamp_to_i64);
+        FaultState { address: self.address, instruction, relative_base: *self.relative_base }
+    }
+
+    pub fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow { state: self.fault_state() }
+    }
+
+    pub fn value(&self, operand: usize) -> &C {
+        &self.operands[operand].1
+    }
+
+    // the address a positional or relative operand refers to
+    pub fn target(&self, operand: usize) -> Option<usize> {
+        self.operands[operand].0
+    }
+
+    // writes the result of a write operand; panics for operands the definition does not declare as written
+    pub fn set(&mut self, operand: usize, value: C) -> Result<(), IntcodeError> {
+        let target = self.operands[operand].0.expect("operand is not a write operand");
+        self.write(target, value)
+    }
+
+    pub fn read(&self, address: usize) -> C {
+        self.storage.cell(address).cloned().unwrap_or_default()
+    }
+
+    pub fn write(&mut self, address: usize, value: C) -> Result<(), IntcodeError> {
+        match self.storage.cell_mut(address) {
+            Some(cell) => {
+                let old = std::mem::replace(cell, value.clone());
+                self.writes.push(MemoryWrite { address, old, new: value });
+                Ok(())
+            },
+            None => Err(IntcodeError::AddressOutOfRange { target: address, state: self.fault_state() })
+        }
+    }
+
+    // converts a cell into an address with the same errors the standard jumps report
+    pub fn to_address(&self, value: &C) -> Result<usize, IntcodeError> {
+        match value.to_i64() {
+            Some(target) if target >= 0 => Ok(target as usize),
+            Some(target) => Err(IntcodeError::NegativeAddress { target, state: self.fault_state() }),
+            None if *value < C::default() =>
+                Err(IntcodeError::NegativeAddress { target: i64::MIN, state: self.fault_state() }),
+            None => Err(IntcodeError::AddressOutOfRange { target: usize::MAX, state: self.fault_state() })
+        }
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        *self.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        *self.relative_base = relative_base;
+    }
+
+    // A Machine turns InputExhausted into Status::NeedsInput and runs the instruction again once there is input,
+    // as long as nothing was written yet; read the input before writing.
+    pub fn input(&mut self) -> Result<C, IntcodeError> {
+        match self.input.try_get_next() {
+            Some(value) => Ok(value),
+            None => Err(IntcodeError::InputExhausted { state: self.fault_state() })
+        }
+    }
+
+    pub fn output(&mut self, value: C) {
+        self.output.output(value);
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Semantics<C: Cell = i64> {
+    // a standard opcode, executed by the built-in interpreter
+    Builtin(Opcode),
+    Custom(fn(&mut Context<C>) -> Result<Effect<C>, IntcodeError>)
+}
+
+#[derive(Clone, Debug)]
+pub struct Definition<C: Cell = i64> {
+    pub code: i64,
+    pub mnemonic: &'static str,
+    pub arity: usize,
+    // indices of the operands that are write targets, those can not be immediate
+    pub writes: Vec<usize>,
+    pub semantics: Semantics<C>
+}
+
+// mode digits have to fit into an i64 next to the two opcode digits
+const MAX_ARITY: usize = 16;
+
+impl<C: Cell> Definition<C> {
+    pub fn standard(opcode: Opcode) -> Definition<C> {
+        let writes = if opcode.writes() { vec![opcode.param_count() - 1] } else { Vec::new() };
+        Definition { code: opcode.code(), mnemonic: opcode.mnemonic(), arity: opcode.param_count(), writes,
+            semantics: Semantics::Builtin(opcode) }
+    }
+
+    // the modes of all operands of an instruction cell, or the first digit that is not a valid mode there
+    pub fn modes(&self, value: i64) -> Result<Vec<Mode>, i64> {
+        let mut modes = Vec::with_capacity(self.arity);
+        let mut divisor = 100;
+        for i in 0..self.arity {
+            let digit = (value / divisor) % 10;
+            divisor *= 10;
+            match Mode::from_digit(digit) {
+                Some(Mode::Immediate) if self.writes.contains(&i) => return Err(digit),
+                Some(mode) => modes.push(mode),
+                None => return Err(digit)
+            }
+        }
+        Ok(modes)
+    }
+
+    pub fn encode(&self, modes: &[Mode]) -> i64 {
+        let mut value = self.code;
+        let mut factor = 100;
+        for mode in modes {
+            value += mode.digit() * factor;
+            factor *= 10;
+        }
+        value
+    }
+}
+
+// A table of opcodes and what they do, used by Machine, the debugger and the assembler and disassembler. The
+// standard opcodes run on the built-in interpreter; a set that only has those is as fast as the plain interpreter.
+#[derive(Clone, Debug)]
+pub struct InstructionSet<C: Cell = i64> {
+    definitions: BTreeMap<i64, Definition<C>>,
+    standard: bool
+}
+
+impl<C: Cell> Default for InstructionSet<C> {
+    fn default() -> Self {
+        InstructionSet::standard()
+    }
+}
+
+impl<C: Cell> InstructionSet<C> {
+    pub fn empty() -> InstructionSet<C> {
+        InstructionSet { definitions: BTreeMap::new(), standard: false }
+    }
+
+    // opcodes 1 to 9 and 99
+    pub fn standard() -> InstructionSet<C> {
+        let mut set = InstructionSet::empty();
+        for opcode in Opcode::all() {
+            set.register(Definition::standard(*opcode));
+        }
+        set
+    }
+
+    // adds or replaces an opcode and returns the definition it replaced
+    pub fn register(&mut self, definition: Definition<C>) -> Option<Definition<C>> {
+        assert!(definition.code > 0 && definition.code < 100, "opcode {} is not in 1..=99", definition.code);
+        assert!(definition.arity <= MAX_ARITY, "{} has more than {} operands", definition.mnemonic, MAX_ARITY);
+        assert!(definition.writes.iter().all(|&operand| operand < definition.arity),
+            "{} writes an operand it does not have", definition.mnemonic);
+        if let Semantics::Builtin(opcode) = definition.semantics {
+            let standard = Definition::<C>::standard(opcode);
+            assert!(definition.code == standard.code && definition.arity == standard.arity
+                && definition.writes == standard.writes, "{} does not match the built-in {}", definition.mnemonic,
+                opcode.mnemonic());
+        }
+        let replaced = self.definitions.insert(definition.code, definition);
+        self.update_standard();
+        replaced
+    }
+
+    pub fn remove(&mut self, code: i64) -> Option<Definition<C>> {
+        let removed = self.definitions.remove(&code);
+        self.update_standard();
+        removed
+    }
+
+    fn update_standard(&mut self) {
+        self.standard = self.definitions.len() == Opcode::all().len()
+            && self.definitions.values().all(|definition| matches!(definition.semantics, Semantics::Builtin(_)));
+    }
+
+    // whether the set has exactly the standard opcodes
+    pub fn is_standard(&self) -> bool {
+        self.standard
+    }
+
+    pub fn get(&self, code: i64) -> Option<&Definition<C>> {
+        self.definitions.get(&code)
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &Definition<C>> {
+        self.definitions.values()
+    }
+
+    // the standard opcode of an instruction cell if this set runs it on the built-in interpreter
+    pub fn builtin(&self, value: &C) -> Option<Opcode> {
+        let code = value.to_i64()? % 100;
+        if self.standard {
+            return Opcode::from_code(code);
+        }
+        match self.definitions.get(&code)?.semantics {
+            Semantics::Builtin(opcode) => Some(opcode),
+            Semantics::Custom(_) => None
+        }
+    }
+
+    // the modes and raw values of the operands an instruction reads, if it can be decoded
+    pub(crate) fn read_operands<S: Storage<Cell = C>>(&self, memory: &Memory<S>, address: usize) -> Vec<(Mode, C)> {
+        if self.builtin(&memory[address]).is_some() {
+            return match fetch_instruction(memory, address) {
+                Ok((instruction, params)) => {
+                    let opcode = instruction.opcode;
+                    let reads = opcode.param_count() - if opcode.writes() { 1 } else { 0 };
+                    instruction.modes.iter().copied().zip(params.iter().cloned()).take(reads).collect()
+                },
+                Err(_) => Vec::new()
+            };
+        }
+        let value = memory[address].to_i64().unwrap_or(-1);
+        let definition = match self.definitions.get(&(value % 100)) {
+            Some(definition) => definition,
+            None => return Vec::new()
+        };
+        let modes = definition.modes(value).unwrap_or_default();
+        modes.into_iter().enumerate().filter(|(i, _)| !definition.writes.contains(i))
+            .map(|(i, mode)| (mode, memory[address + 1 + i].clone())).collect()
+    }
+
+    pub fn try_execute_instruction<S: Storage<Cell = C>, I: Input<C>, O: Output<C>>(&self, memory: &mut Memory<S>, input: &mut I, output: &mut O, address: &mut usize) -> Result<Progress<C>, IntcodeError> {
+        if self.builtin(&memory[*address]).is_some() {
+            return match try_execute_builtin(memory, input, output, address)? {
+                true => Ok(Progress::Running),
+                false => Ok(Progress::Halted(None))
+            };
+        }
+        self.execute_custom(memory, input, output, address, &mut Vec::new())
+    }
+
+    // runs an instruction that is not built in and collects the cells it wrote
+    pub(crate) fn execute_custom<S: Storage<Cell = C>, I: Input<C>, O: Output<C>>(&self, memory: &mut Memory<S>, input: &mut I, output: &mut O, address: &mut usize, writes: &mut Vec<MemoryWrite<C>>) -> Result<Progress<C>, IntcodeError> {
+        let start = *address;
+        let value = memory[start].to_i64().unwrap_or(-1);
+        let code = value % 100;
+        let (definition, semantics) = match self.definitions.get(&code) {
+            Some(definition @ Definition { semantics: Semantics::Custom(semantics), .. }) => (definition, *semantics),
+            _ => return Err(IntcodeError::InvalidOpcode { opcode: memory[start].clamp_to_i64() % 100,
+                state: fault_state(memory, start) })
+        };
+        let modes = definition.modes(value)
+            .map_err(|digit| IntcodeError::InvalidMode { mode: digit, state: fault_state(memory, start) })?;
+        let mut operands = Vec::with_capacity(definition.arity);
+        for (i, mode) in modes.into_iter().enumerate() {
+            let raw = memory[start + 1 + i].clone();
+            if definition.writes.contains(&i) {
+                let target = match mode {
+                    Mode::Relative => relative_address(memory, start, &raw)?,
+                    Mode::Position | Mode::Immediate => cell_address(memory, start, &raw)?
+                };
+                operands.push((Some(target), memory[target].clone()));
+            } else {
+                let operand = read_operand(memory, start, &raw, mode)?;
+                operands.push((operand.address, operand.value));
+            }
+        }
+
+        let next = start + 1 + definition.arity;
+        let mut context = Context { address: start, operands: &operands, storage: &mut memory.storage,
+            relative_base: &mut memory.relative_base, input, output, writes };
+        match semantics(&mut context)? {
+            Effect::Continue => *address = next,
+            Effect::Jump(target) => *address = target,
+            Effect::Halt(code) => return Ok(Progress::Halted(code))
+        }
+        Ok(Progress::Running)
+    }
+
+    // runs a tape to completion and returns the final memory together with the exit code of the halting instruction
+    pub fn try_execute_intcode<I: Input<C>, O: Output<C>>(&self, tape: &[C], input: &mut I, output: &mut O) -> Result<(Vec<C>, Option<C>), IntcodeError> {
+        let mut memory = Memory::new(tape);
+        let mut address = 0;
+        loop {
+            if let Progress::Halted(code) = self.try_execute_instruction(&mut memory, input, output, &mut address)? {
+                return Ok((memory.into_cells(), code));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble_with;
+    use crate::debugger::Debugger;
+    use crate::debugger::StopReason;
+    use crate::disasm::listing_with;
+    use crate::isa::Context;
+    use crate::isa::Definition;
+    use crate::isa::Effect;
+    use crate::isa::InstructionSet;
+    use crate::isa::Semantics;
+    use crate::try_execute_intcode;
+    use crate::FaultState;
+    use crate::IntcodeError;
+    use crate::Machine;
+    use crate::MemoryWrite;
+    use crate::Status;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    #[test]
+    fn standard_set_matches_interpreter() {
+        let programs = vec![
+            vec![1, 0, 0, 3, 99],
+            vec![1102, 5, 2, 3, 99],
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            vec![3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0, 1002, 21,
+                125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99],
+            vec![109, 15, 109, 19, 204, -34, 99],
+            vec![1, 0, 0, 3],
+            vec![11101, 1, 1, 3, 99],
+            vec![3, 3, 99]
+        ];
+        let set = InstructionSet::standard();
+        for program in programs {
+            let mut expected = VecOutput::new();
+            let mut actual = VecOutput::new();
+            let memory = try_execute_intcode(&program, &mut VecInput::new(vec![7]), &mut expected);
+            let result = set.try_execute_intcode(&program, &mut VecInput::new(vec![7]), &mut actual);
+            assert_eq!(result.map(|(memory, _)| memory), memory);
+            assert_eq!(actual.values(), expected.values());
+        }
+    }
+
+    fn halt_with_code(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        Ok(Effect::Halt(Some(*context.value(0))))
+    }
+
+    // copies value(2) cells from the address in value(0) to the address in value(1)
+    fn memory_copy(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        let source = context.to_address(context.value(0))?;
+        let destination = context.to_address(context.value(1))?;
+        let count = context.to_address(context.value(2))?;
+        let cells: Vec<i64> = (source..source + count).map(|address| context.read(address)).collect();
+        for (i, value) in cells.into_iter().enumerate() {
+            context.write(destination + i, value)?;
+        }
+        Ok(Effect::Continue)
+    }
+
+    #[test]
+    fn custom_opcodes() {
+        let mut set = InstructionSet::standard();
+        set.register(Definition { code: 98, mnemonic: "HLTC", arity: 1, writes: Vec::new(),
+            semantics: Semantics::Custom(halt_with_code) });
+        set.register(Definition { code: 20, mnemonic: "COPY", arity: 3, writes: Vec::new(),
+            semantics: Semantics::Custom(memory_copy) });
+        // copies three cells from 9 to 12, prints the middle one and exits with the last one
+        let tape = vec![11120, 9, 12, 3, 4, 13, 98, 14, 0, 5, 6, 7, 0, 0, 0];
+        let mut out = VecOutput::new();
+        let (memory, code) = set.try_execute_intcode(&tape, &mut VecInput::new(vec![]), &mut out).unwrap();
+        assert_eq!(out.values(), &vec![6]);
+        assert_eq!(code, Some(7));
+        assert_eq!(&memory[12..], &[5, 6, 7]);
+    }
+
+    #[test]
+    fn unknown_opcodes() {
+        let mut set = InstructionSet::standard();
+        assert!(set.remove(2).is_some());
+        let result = set.try_execute_intcode(&[1102, 5, 2, 3, 99], &mut VecInput::new(vec![]), &mut VecOutput::new());
+        assert_eq!(result, Err(IntcodeError::InvalidOpcode { opcode: 2,
+            state: FaultState { address: 0, instruction: 1102, relative_base: 0 } }));
+        let result = set.try_execute_intcode(&[11101, 1, 1, 3, 99], &mut VecInput::new(vec![]), &mut VecOutput::new());
+        assert_eq!(result, Err(IntcodeError::InvalidMode { mode: 1,
+            state: FaultState { address: 0, instruction: 11101, relative_base: 0 } }));
+    }
+
+    fn output_pair(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        let (first, second) = (*context.value(0), *context.value(1));
+        context.output(first);
+        context.output(second);
+        Ok(Effect::Continue)
+    }
+
+    fn read(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        let value = context.input()?;
+        context.set(0, value)?;
+        Ok(Effect::Continue)
+    }
+
+    #[test]
+    fn machine_and_tools() {
+        let mut set = InstructionSet::standard();
+        set.register(Definition { code: 98, mnemonic: "HLTC", arity: 1, writes: Vec::new(),
+            semantics: Semantics::Custom(halt_with_code) });
+        set.register(Definition { code: 20, mnemonic: "COPY", arity: 3, writes: Vec::new(),
+            semantics: Semantics::Custom(memory_copy) });
+        set.register(Definition { code: 21, mnemonic: "PAIR", arity: 2, writes: Vec::new(),
+            semantics: Semantics::Custom(output_pair) });
+        set.register(Definition { code: 22, mnemonic: "READ", arity: 1, writes: vec![0],
+            semantics: Semantics::Custom(read) });
+        let tape = assemble_with("
+                    COPY #source, #target, #3
+                    PAIR [target], [target+2]
+                    READ -> [target]
+                    PAIR [target], #0
+                    HLTC [target+1]
+                    HLT
+            source: DATA 5, 6, 7
+            target: DATA 0, 0, 0
+        ", &set).unwrap();
+        let listing = listing_with(&tape, &set);
+        assert!(listing.starts_with("0000: COPY #15, #18, #3\n0004: PAIR [18], [20]\n0007: READ -> [18]\n"));
+        assert_eq!(assemble_with(&listing, &set), Ok(tape.clone()));
+
+        let mut machine = Machine::new(&tape);
+        assert!(matches!(machine.run(), Err(IntcodeError::InvalidOpcode { opcode: 20, .. })));
+        let mut machine = Machine::new(&tape);
+        machine.set_instruction_set(set.clone());
+        assert_eq!(machine.run_to_block(), Ok((vec![5, 7], Status::NeedsInput)));
+        machine.push_input(9);
+        assert_eq!(machine.run_to_block(), Ok((vec![9, 0], Status::Halted)));
+        assert_eq!(machine.exit_code(), Some(&6));
+
+        let mut debugger = Debugger::new(&tape, VecInput::new(vec![9]), VecOutput::new());
+        debugger.set_instruction_set(set);
+        assert_eq!(debugger.current_instruction(), "0000: COPY #15, #18, #3");
+        assert_eq!(debugger.cont(), StopReason::Halted);
+        assert_eq!(debugger.output_mut().values(), &vec![5, 7, 9, 0]);
+    }
+
+    // stores value(0) in the target and prints it twice
+    fn store_and_echo(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        let value = *context.value(0);
+        context.set(1, value)?;
+        context.output(value);
+        context.output(value);
+        Ok(Effect::Continue)
+    }
+
+    #[test]
+    fn watchpoints_after_outputs() {
+        let mut set = InstructionSet::standard();
+        set.register(Definition { code: 23, mnemonic: "ECHO", arity: 2, writes: vec![1],
+            semantics: Semantics::Custom(store_and_echo) });
+        let mut machine = Machine::new(&[123, 5, 6, 104, 1, 99, 0]);
+        machine.set_instruction_set(set);
+        machine.add_watchpoint(6);
+        assert_eq!(machine.run(), Ok(Status::Output(5)));
+        assert_eq!(machine.run(), Ok(Status::Output(5)));
+        // reported before the OUT runs
+        assert_eq!(machine.run(), Ok(Status::Watchpoint(MemoryWrite { address: 6, old: 0, new: 5 })));
+        assert_eq!(machine.address(), 3);
+        assert_eq!(machine.run_to_block(), Ok((vec![1], Status::Halted)));
+    }
+}
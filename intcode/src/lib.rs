@@ -1,27 +1,110 @@
 use std::io;
-use std::io::BufReader;
-use std::io::BufRead;
-use std::fs::File;
 use std::ops::Index;
 use std::ops::IndexMut;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::error;
+use std::fmt;
 
-pub fn load_tape(input: File) -> Vec<i64> {
-    let reader = BufReader::new(&input);
-    let mut tape = Vec::new();
-    for opcode in reader.split(b',') {
-        let op_str = String::from_utf8(opcode.unwrap()).unwrap();
-        let op: i64 = op_str.trim_end().parse().unwrap();
-        tape.push(op);
+pub mod adapters;
+pub mod asm;
+pub mod bigint;
+pub mod cache;
+pub mod cell;
+pub mod cfg;
+pub mod coverage;
+pub mod debugger;
+pub mod disasm;
+pub mod history;
+pub mod isa;
+pub mod limits;
+pub mod mmio;
+pub mod network;
+pub mod pipe;
+pub mod profile;
+pub mod search;
+pub mod snapshot;
+pub mod storage;
+pub mod tape;
+pub mod topology;
+pub mod transpile;
+
+pub use adapters::extend_output;
+pub use adapters::input_fn;
+pub use adapters::Chain;
+pub use adapters::ExtendOutput;
+pub use adapters::FnInput;
+pub use adapters::Tee;
+pub use bigint::BigInt;
+pub use cache::DecodeCache;
+use cache::try_execute_instruction_cached;
+pub use cell::Cell;
+pub use cell::Checked;
+pub use limits::Limits;
+use limits::LoopDetector;
+use limits::Stop;
+use history::UndoLog;
+pub use isa::InstructionSet;
+use isa::Progress;
+pub use mmio::Device;
+use mmio::MemoryMap;
+pub use network::Network;
+pub use pipe::cell_pipe;
+pub use pipe::pipe;
+pub use pipe::PipeInput;
+pub use pipe::PipeOutput;
+pub use profile::Profile;
+pub use search::Search;
+pub use snapshot::Snapshot;
+pub use storage::DenseStorage;
+pub use storage::PagedStorage;
+pub use storage::Storage;
+pub use tape::parse_tape;
+pub use tape::save_binary_tape;
+pub use tape::save_tape;
+pub use tape::try_load_binary_tape;
+pub use tape::try_load_tape;
+pub use tape::Tape;
+pub use tape::TapeError;
+pub use topology::Topology;
+
+// panics with the position of the first malformed value, see try_load_tape for the format
+pub fn load_tape<R: io::Read>(input: R) -> Vec<i64> {
+    match try_load_tape(input) {
+        Ok(tape) => tape,
+        Err(error) => panic!("{}", error)
+    }
+}
+
+pub fn load_binary_tape<R: io::Read>(input: R) -> Vec<i64> {
+    match try_load_binary_tape(input) {
+        Ok(tape) => tape,
+        Err(error) => panic!("{}", error)
     }
-    tape
 }
 
-pub trait Input {
-    fn get_next(&mut self) -> i64;
+pub trait Input<C: Cell = i64> {
+    fn get_next(&mut self) -> C;
+
+    // returns None once the input has run out instead of panicking
+    fn try_get_next(&mut self) -> Option<C> {
+        Some(self.get_next())
+    }
+
+    // reads from this input until it runs out, then from the next one
+    fn followed_by<I: Input<C>>(self, next: I) -> Chain<Self, I> where Self: Sized {
+        Chain::new(self, next)
+    }
 }
 
-pub trait Output {
-    fn output(&mut self, value: i64);
+pub trait Output<C: Cell = i64> {
+    fn output(&mut self, value: C);
+
+    fn tee<O: Output<C>>(self, other: O) -> Tee<Self, O> where Self: Sized {
+        Tee::new(self, other)
+    }
 }
 
 pub struct StdInput;
@@ -50,6 +133,12 @@ impl StdASCIIInput {
     }
 }
 
+impl Default for StdASCIIInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Input for StdASCIIInput {
     fn get_next(&mut self) -> i64 {
         if self.read_offset >= self.last_input.chars().count() {
@@ -88,6 +177,12 @@ impl StdASCIIOutput {
     }
 }
 
+impl Default for StdASCIIOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Output for StdASCIIOutput {
     fn output(&mut self, value: i64) {
         // write output to stdout
@@ -96,24 +191,36 @@ impl Output for StdASCIIOutput {
     }
 }
 
-pub struct VecInput {
+pub struct VecInput<C: Cell = i64> {
     i: usize,
-    values: Vec<i64>
+    values: Vec<C>
 }
 
 impl VecInput {
     pub fn new(values: Vec<i64>) -> VecInput {
-        VecInput { i: 0, values: values }
+        VecInput::from(values)
     }
 }
 
-impl Input for VecInput {
-    fn get_next(&mut self) -> i64 {
-        if self.i >= self.values.len() {
-            panic!("not enough inputs provided to VecInput ({} requested, {} provided)", self.i + 1, self.values.len())
+impl<C: Cell> From<Vec<C>> for VecInput<C> {
+    fn from(values: Vec<C>) -> VecInput<C> {
+        VecInput { i: 0, values }
+    }
+}
+
+impl<C: Cell> Input<C> for VecInput<C> {
+    fn get_next(&mut self) -> C {
+        match self.try_get_next() {
+            Some(value) => value,
+            None => panic!("not enough inputs provided to VecInput ({} requested, {} provided)", self.i + 1, self.values.len())
+        }
+    }
+
+    fn try_get_next(&mut self) -> Option<C> {
+        let result = self.values.get(self.i).cloned();
+        if result.is_some() {
+            self.i += 1;
         }
-        let result = self.values[self.i];
-        self.i = self.i + 1;
         result
     }
 }
@@ -124,171 +231,1016 @@ pub struct StringInput<'a> {
 
 impl<'a> StringInput<'a> {
     pub fn new(chars: std::str::Chars<'a>) -> StringInput<'a> {
-        StringInput { chars: chars }
+        StringInput { chars }
     }
 }
 
 impl<'a> Input for StringInput<'a> {
     fn get_next(&mut self) -> i64 {
-        self.chars.next().unwrap() as i64
+        self.try_get_next().expect("not enough characters provided to StringInput")
+    }
+
+    fn try_get_next(&mut self) -> Option<i64> {
+        self.chars.next().map(|c| c as i64)
     }
 }
 
-pub struct VecOutput {
-    values: Vec<i64>
+pub struct VecOutput<C: Cell = i64> {
+    values: Vec<C>
 }
 
 impl VecOutput {
     pub fn new() -> VecOutput {
-        VecOutput { values: Vec::new() }
+        VecOutput::default()
     }
+}
 
-    pub fn values(&self) -> &Vec<i64> {
+impl<C: Cell> VecOutput<C> {
+    pub fn values(&self) -> &Vec<C> {
         &self.values
     }
 }
 
-impl Output for VecOutput {
-    fn output(&mut self, value: i64) {
+impl<C: Cell> Default for VecOutput<C> {
+    fn default() -> Self {
+        VecOutput { values: Vec::new() }
+    }
+}
+
+impl<C: Cell> Output<C> for VecOutput<C> {
+    fn output(&mut self, value: C) {
         self.values.push(value);
     }
 }
 
-fn get_param_value(memory: &Memory, address: usize, mode: i64) -> i64 {
-    let param_value = memory[address];
-    match mode {
-        0 => memory[param_value as usize],
-        1 => param_value,
-        2 => memory[(memory.relative_base + param_value) as usize],
-        _ => panic!("invalid read param mode: {})", mode)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FaultState {
+    pub address: usize,
+    pub instruction: i64,
+    pub relative_base: i64
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IntcodeError {
+    InvalidOpcode { opcode: i64, state: FaultState },
+    InvalidMode { mode: i64, state: FaultState },
+    NegativeAddress { target: i64, state: FaultState },
+    AddressOutOfRange { target: usize, state: FaultState },
+    InputExhausted { state: FaultState },
+    // only reported by cell types that check their arithmetic, or when the relative base leaves the i64 range
+    Overflow { state: FaultState }
+}
+
+impl IntcodeError {
+    pub fn state(&self) -> &FaultState {
+        match self {
+            IntcodeError::InvalidOpcode { state, .. } => state,
+            IntcodeError::InvalidMode { state, .. } => state,
+            IntcodeError::NegativeAddress { state, .. } => state,
+            IntcodeError::AddressOutOfRange { state, .. } => state,
+            IntcodeError::InputExhausted { state } => state,
+            IntcodeError::Overflow { state } => state
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::InvalidOpcode { opcode, .. } => write!(f, "invalid opcode: {}", opcode)?,
+            IntcodeError::InvalidMode { mode, .. } => write!(f, "invalid param mode: {}", mode)?,
+            IntcodeError::NegativeAddress { target, .. } => write!(f, "negative address: {}", target)?,
+            IntcodeError::AddressOutOfRange { target, .. } => write!(f, "address out of range: {}", target)?,
+            IntcodeError::InputExhausted { .. } => write!(f, "input exhausted")?,
+            IntcodeError::Overflow { .. } => write!(f, "arithmetic overflow")?
+        }
+        let state = self.state();
+        write!(f, " (full instruction: {}@{}, relative base: {})", state.instruction, state.address, state.relative_base)
+    }
+}
+
+impl error::Error for IntcodeError {}
+
+fn fault_state<S: Storage>(memory: &Memory<S>, address: usize) -> FaultState {
+    FaultState { address, instruction: memory[address].clamp_to_i64(), relative_base: memory.relative_base }
+}
+
+fn overflow<S: Storage>(memory: &Memory<S>, address: usize) -> IntcodeError {
+    IntcodeError::Overflow { state: fault_state(memory, address) }
+}
+
+fn to_address<S: Storage>(memory: &Memory<S>, address: usize, target: i64) -> Result<usize, IntcodeError> {
+    if target < 0 {
+        Err(IntcodeError::NegativeAddress { target, state: fault_state(memory, address) })
+    } else {
+        Ok(target as usize)
+    }
+}
+
+// addresses are computed as i64, so a cell that does not fit is out of range in one direction or the other
+#[inline]
+fn cell_address<S: Storage>(memory: &Memory<S>, address: usize, target: &S::Cell) -> Result<usize, IntcodeError> {
+    match target.to_i64() {
+        Some(target) => to_address(memory, address, target),
+        None if *target < S::Cell::default() =>
+            Err(IntcodeError::NegativeAddress { target: i64::MIN, state: fault_state(memory, address) }),
+        None => Err(IntcodeError::AddressOutOfRange { target: usize::MAX, state: fault_state(memory, address) })
+    }
+}
+
+#[inline]
+fn relative_address<S: Storage>(memory: &Memory<S>, address: usize, offset: &S::Cell) -> Result<usize, IntcodeError> {
+    let target = offset.to_i64().and_then(|offset| memory.relative_base.checked_add(offset));
+    match target {
+        Some(target) => to_address(memory, address, target),
+        None => Err(overflow(memory, address))
     }
 }
 
-fn set_memory_value(memory: &mut Memory, address: i64, mode: i64, value: i64) {
-    let relative_base = memory.relative_base;
-    match mode {
-        0 => memory[address as usize] = value,
-        2 => memory[(relative_base + address) as usize] = value,
-        _ => panic!("invalid write param mode: {})", mode)
+#[inline]
+fn read_operand<S: Storage>(memory: &Memory<S>, address: usize, raw: &S::Cell, mode: Mode) -> Result<Operand<S::Cell>, IntcodeError> {
+    let target = match mode {
+        Mode::Position => Some(cell_address(memory, address, raw)?),
+        Mode::Immediate => None,
+        Mode::Relative => Some(relative_address(memory, address, raw)?)
+    };
+    let value = match target {
+        Some(target) => memory[target].clone(),
+        None => raw.clone()
     };
+    Ok(Operand { mode, raw: raw.clone(), address: target, value })
+}
+
+#[inline]
+fn write_operand<C: Cell, S: Storage<Cell = C>>(memory: &mut Memory<S>, address: usize, raw: &C, mode: Mode, value: C) -> Result<(Operand<C>, MemoryWrite<C>), IntcodeError> {
+    let target = match mode {
+        Mode::Relative => relative_address(memory, address, raw)?,
+        Mode::Position | Mode::Immediate => cell_address(memory, address, raw)?
+    };
+    let cell = match memory.storage.cell_mut(target) {
+        Some(cell) => cell,
+        None => return Err(IntcodeError::AddressOutOfRange { target, state: fault_state(memory, address) })
+    };
+    let old = std::mem::replace(cell, value.clone());
+    Ok((Operand { mode, raw: raw.clone(), address: Some(target), value: value.clone() },
+        MemoryWrite { address: target, old, new: value }))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt
+}
+
+const OPCODES: [Opcode; 10] = [Opcode::Add, Opcode::Multiply, Opcode::Input, Opcode::Output, Opcode::JumpIfTrue,
+    Opcode::JumpIfFalse, Opcode::LessThan, Opcode::Equals, Opcode::AdjustRelativeBase, Opcode::Halt];
+
+impl Opcode {
+    pub fn all() -> &'static [Opcode] {
+        &OPCODES
+    }
+
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        OPCODES.iter().copied().find(|opcode| opcode.code() == code)
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Multiply => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "ADD",
+            Opcode::Multiply => "MUL",
+            Opcode::Input => "IN",
+            Opcode::Output => "OUT",
+            Opcode::JumpIfTrue => "JT",
+            Opcode::JumpIfFalse => "JF",
+            Opcode::LessThan => "LT",
+            Opcode::Equals => "EQ",
+            Opcode::AdjustRelativeBase => "ARB",
+            Opcode::Halt => "HLT"
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        OPCODES.iter().copied().find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
+
+    pub fn param_count(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0
+        }
+    }
+
+    // whether the last parameter is the address the result gets written to
+    pub fn writes(self) -> bool {
+        matches!(self, Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals | Opcode::Input)
+    }
 }
 
-pub struct Memory {
-    memory: Vec<i64>,
-    relative_base: i64
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative
 }
 
-impl Memory {
-    pub fn new(memory: &[i64]) -> Memory {
-        Memory { memory: memory.to_vec(), relative_base: 0 }
+impl Mode {
+    pub fn from_digit(digit: i64) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None
+        }
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2
+        }
     }
 }
 
-impl Index<usize> for Memory {
-    type Output = i64;
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    InvalidOpcode(i64),
+    InvalidMode(i64)
+}
 
-    fn index(&self, index: usize) -> &Self::Output {
-        if index < self.memory.len() {
-            &self.memory[index]
-        } else {
-            &0
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: [Mode; 3]
+}
+
+impl Instruction {
+    // only the modes of parameters the opcode actually uses are validated, the others are ignored
+    pub fn decode(value: i64) -> Result<Instruction, DecodeError> {
+        let opcode = match Opcode::from_code(value % 100) {
+            Some(opcode) => opcode,
+            None => return Err(DecodeError::InvalidOpcode(value % 100))
+        };
+        let mut modes = [Mode::Position; 3];
+        let mut divisor = 100;
+        for (i, mode) in modes.iter_mut().enumerate().take(opcode.param_count()) {
+            let digit = (value / divisor) % 10;
+            *mode = match Mode::from_digit(digit) {
+                Some(Mode::Immediate) if opcode.writes() && i == opcode.param_count() - 1 =>
+                    return Err(DecodeError::InvalidMode(digit)),
+                Some(mode) => mode,
+                None => return Err(DecodeError::InvalidMode(digit))
+            };
+            divisor *= 10;
+        }
+        Ok(Instruction { opcode, modes })
+    }
+
+    pub fn encode(&self) -> i64 {
+        let mut value = self.opcode.code();
+        let mut factor = 100;
+        for mode in self.modes.iter().take(self.opcode.param_count()) {
+            value += mode.digit() * factor;
+            factor *= 10;
+        }
+        value
+    }
+
+    pub fn len(&self) -> usize {
+        self.opcode.param_count() + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memory<S: Storage = DenseStorage> {
+    storage: S,
+    relative_base: i64,
+    // returned for cells the storage does not hold yet
+    zero: S::Cell
+}
+
+impl<C: Cell> Memory<DenseStorage<C>> {
+    pub fn new(memory: &[C]) -> Memory<DenseStorage<C>> {
+        Memory::from_storage(DenseStorage::new(memory))
+    }
+
+    // all cells up to the highest address that has been written so far
+    pub fn cells(&self) -> &[C] {
+        self.storage.cells()
+    }
+
+    pub fn into_cells(self) -> Vec<C> {
+        self.storage.into_cells()
+    }
+}
+
+impl<S: Storage> Memory<S> {
+    pub fn from_storage(storage: S) -> Memory<S> {
+        Memory { storage, relative_base: 0, zero: S::Cell::default() }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.relative_base = relative_base;
+    }
+
+    // returns false instead of panicking if the storage can not hold the address
+    pub fn write(&mut self, address: usize, value: S::Cell) -> bool {
+        match self.storage.cell_mut(address) {
+            Some(cell) => {
+                *cell = value;
+                true
+            },
+            None => false
         }
     }
 }
 
-impl IndexMut<usize> for Memory {
+impl<S: Storage> Index<usize> for Memory<S> {
+    type Output = S::Cell;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.storage.cell(index).unwrap_or(&self.zero)
+    }
+}
+
+impl<S: Storage> IndexMut<usize> for Memory<S> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        if index >= self.memory.len() {
-            let required_mem = index - self.memory.len() + 1;
-            for _ in 0..required_mem {
-                self.memory.push(0);
+        match self.storage.cell_mut(index) {
+            Some(cell) => cell,
+            None => panic!("address {} is out of range for this memory", index)
+        }
+    }
+}
+
+// the resolved value of a parameter; positional and relative parameters also carry the address they refer to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Operand<C: Cell = i64> {
+    pub mode: Mode,
+    pub raw: C,
+    pub address: Option<usize>,
+    pub value: C
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryWrite<C: Cell = i64> {
+    pub address: usize,
+    pub old: C,
+    pub new: C
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Step<C: Cell = i64> {
+    pub address: usize,
+    pub instruction: Instruction,
+    operands: [Option<Operand<C>>; 3],
+    pub write: Option<MemoryWrite<C>>,
+    pub next_address: usize,
+    pub relative_base: i64
+}
+
+impl<C: Cell> Step<C> {
+    pub fn operands(&self) -> impl Iterator<Item = &Operand<C>> {
+        self.operands.iter().flatten()
+    }
+
+    pub fn operand(&self, i: usize) -> Option<&Operand<C>> {
+        self.operands.get(i).and_then(|operand| operand.as_ref())
+    }
+}
+
+// hooks that get called around every instruction; the default methods do nothing, so a run without an observer
+// compiles down to the plain interpreter
+pub trait Observer<C: Cell = i64> {
+    fn before_instruction<S: Storage<Cell = C>>(&mut self, _address: usize, _instruction: &Instruction, _memory: &Memory<S>) {}
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, _step: &Step<C>, _memory: &Memory<S>) {}
+    // a cell that changed from outside of the program, e.g. a mapped device answering a read of the next instruction
+    fn external_write<S: Storage<Cell = C>>(&mut self, _write: &MemoryWrite<C>, _memory: &Memory<S>) {}
+}
+
+pub struct NoObserver;
+
+impl<C: Cell> Observer<C> for NoObserver {}
+
+impl<C: Cell, B: Observer<C>> Observer<C> for &mut B {
+    fn before_instruction<S: Storage<Cell = C>>(&mut self, address: usize, instruction: &Instruction, memory: &Memory<S>) {
+        (**self).before_instruction(address, instruction, memory);
+    }
+
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, memory: &Memory<S>) {
+        (**self).after_instruction(step, memory);
+    }
+
+    fn external_write<S: Storage<Cell = C>>(&mut self, write: &MemoryWrite<C>, memory: &Memory<S>) {
+        (**self).external_write(write, memory);
+    }
+}
+
+// runs both observers, the first one before the second
+impl<C: Cell, A: Observer<C>, B: Observer<C>> Observer<C> for (A, B) {
+    fn before_instruction<S: Storage<Cell = C>>(&mut self, address: usize, instruction: &Instruction, memory: &Memory<S>) {
+        self.0.before_instruction(address, instruction, memory);
+        self.1.before_instruction(address, instruction, memory);
+    }
+
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, memory: &Memory<S>) {
+        self.0.after_instruction(step, memory);
+        self.1.after_instruction(step, memory);
+    }
+
+    fn external_write<S: Storage<Cell = C>>(&mut self, write: &MemoryWrite<C>, memory: &Memory<S>) {
+        self.0.external_write(write, memory);
+        self.1.external_write(write, memory);
+    }
+}
+
+// an observer that can be switched off
+impl<C: Cell, B: Observer<C>> Observer<C> for Option<B> {
+    fn before_instruction<S: Storage<Cell = C>>(&mut self, address: usize, instruction: &Instruction, memory: &Memory<S>) {
+        if let Some(observer) = self {
+            observer.before_instruction(address, instruction, memory);
+        }
+    }
+
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, memory: &Memory<S>) {
+        if let Some(observer) = self {
+            observer.after_instruction(step, memory);
+        }
+    }
+
+    fn external_write<S: Storage<Cell = C>>(&mut self, write: &MemoryWrite<C>, memory: &Memory<S>) {
+        if let Some(observer) = self {
+            observer.external_write(write, memory);
+        }
+    }
+}
+
+// decodes the instruction at the given address together with the raw values of its parameters
+#[inline]
+pub fn fetch_instruction<S: Storage>(memory: &Memory<S>, address: usize) -> Result<(Instruction, [S::Cell; 3]), IntcodeError> {
+    let value = memory[address].to_i64().ok_or_else(|| IntcodeError::InvalidOpcode {
+        opcode: memory[address].clamp_to_i64(), state: fault_state(memory, address) })?;
+    let instruction = match Instruction::decode(value) {
+        Ok(instruction) => instruction,
+        Err(DecodeError::InvalidOpcode(opcode)) =>
+            return Err(IntcodeError::InvalidOpcode { opcode, state: fault_state(memory, address) }),
+        Err(DecodeError::InvalidMode(mode)) =>
+            return Err(IntcodeError::InvalidMode { mode, state: fault_state(memory, address) })
+    };
+    let mut params: [S::Cell; 3] = Default::default();
+    for (i, param) in params.iter_mut().enumerate().take(instruction.opcode.param_count()) {
+        *param = memory[address + 1 + i].clone();
+    }
+    Ok((instruction, params))
+}
+
+// executes an already fetched instruction, the parameters are not read from memory again
+pub fn execute_fetched<S: Storage, I: Input<S::Cell>, O: Output<S::Cell>, B: Observer<S::Cell>>(memory: &mut Memory<S>, input: &mut I, output: &mut O, address: &mut usize, instruction: Instruction, params: [S::Cell; 3], observer: &mut B) -> Result<Step<S::Cell>, IntcodeError> {
+    let start = *address;
+    observer.before_instruction(start, &instruction, memory);
+    let [mode1, mode2, mode3] = instruction.modes;
+    let mut operands = [None, None, None];
+    let mut write = None;
+    match instruction.opcode {
+        Opcode::Halt => {},
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+            let param1 = read_operand(memory, start, &params[0], mode1)?;
+            let param2 = read_operand(memory, start, &params[1], mode2)?;
+            let result = match instruction.opcode {
+                Opcode::Add => param1.value.try_add(&param2.value).ok_or_else(|| overflow(memory, start))?,
+                Opcode::Multiply => param1.value.try_mul(&param2.value).ok_or_else(|| overflow(memory, start))?,
+                Opcode::LessThan => S::Cell::from_i64(if param1.value < param2.value { 1 } else { 0 }),
+                _ => S::Cell::from_i64(if param1.value == param2.value { 1 } else { 0 })
+            };
+            let (param3, memory_write) = write_operand(memory, start, &params[2], mode3, result)?;
+            operands = [Some(param1), Some(param2), Some(param3)];
+            write = Some(memory_write);
+            *address += 4;
+        },
+        Opcode::Input => {
+            let value = match input.try_get_next() {
+                Some(value) => value,
+                None => return Err(IntcodeError::InputExhausted { state: fault_state(memory, start) })
+            };
+            let (param1, memory_write) = write_operand(memory, start, &params[0], mode1, value)?;
+            operands[0] = Some(param1);
+            write = Some(memory_write);
+            *address += 2;
+        },
+        Opcode::Output => {
+            let param1 = read_operand(memory, start, &params[0], mode1)?;
+            output.output(param1.value.clone());
+            operands[0] = Some(param1);
+            *address += 2;
+        },
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let param1 = read_operand(memory, start, &params[0], mode1)?;
+            let param2 = read_operand(memory, start, &params[1], mode2)?;
+            if (param1.value != S::Cell::default()) == (instruction.opcode == Opcode::JumpIfTrue) {
+                *address = cell_address(memory, start, &param2.value)?;
+            } else {
+                *address += 3;
             }
+            operands = [Some(param1), Some(param2), None];
+        },
+        Opcode::AdjustRelativeBase => {
+            let param1 = read_operand(memory, start, &params[0], mode1)?;
+            memory.relative_base = param1.value.to_i64().and_then(|offset| memory.relative_base.checked_add(offset))
+                .ok_or_else(|| overflow(memory, start))?;
+            operands[0] = Some(param1);
+            *address += 2;
         }
-        &mut self.memory[index]
-    }
-}
-
-pub fn execute_instruction<I: Input, O: Output>(memory: &mut Memory, input: &mut I, output: &mut O, address: &mut usize) -> bool {
-    let instruction = memory[*address];
-    let opcode = instruction % 100;
-    let mode1 = (instruction / 100) % 10;
-    let mode2 = (instruction / 1000) % 10;
-    let mode3 = (instruction / 10000) % 10;
-    if opcode == 99 {
-        return false;
-    } else if opcode == 1 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        let param2 = get_param_value(memory, *address + 2, mode2);
-        let out_address = memory[*address + 3];
-        set_memory_value(memory, out_address, mode3, param1 + param2);
-        *address += 4;
-    } else if opcode == 2 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        let param2 = get_param_value(memory, *address + 2, mode2);
-        let out_address = memory[*address + 3];
-        set_memory_value(memory, out_address, mode3, param1 * param2);
-        *address += 4;
-    } else if opcode == 3 {
-        let out_address = memory[*address + 1];
-        set_memory_value(memory, out_address, mode1, input.get_next());
-        *address += 2;
-    } else if opcode == 4 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        output.output(param1);
-        *address += 2;
-    } else if opcode == 5 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        let param2 = get_param_value(memory, *address + 2, mode2);
-        if param1 != 0 {
-            *address = param2 as usize;
-        } else {
-            *address += 3;
-        }
-    } else if opcode == 6 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        let param2 = get_param_value(memory, *address + 2, mode2);
-        if param1 == 0 {
-            *address = param2 as usize;
-        } else {
-            *address += 3;
-        }
-    } else if opcode == 7 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        let param2 = get_param_value(memory, *address + 2, mode2);
-        let out_address = memory[*address + 3];
-        if param1 < param2 {
-            set_memory_value(memory, out_address, mode3, 1);
-        } else {
-            set_memory_value(memory, out_address, mode3, 0);
-        }
-        *address += 4;
-    } else if opcode == 8 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        let param2 = get_param_value(memory, *address + 2, mode2);
-        let out_address = memory[*address + 3];
-        if param1 == param2 {
-            set_memory_value(memory, out_address, mode3, 1);
-        } else {
-            set_memory_value(memory, out_address, mode3, 0);
-        }
-        *address += 4;
-    } else if opcode == 9 {
-        let param1 = get_param_value(memory, *address + 1, mode1);
-        memory.relative_base += param1;
-        *address += 2;
-    } else {
-        panic!("invalid opcode: {} (full instruction: {}@{})", opcode, instruction, *address);
     }
-    true
+    let step = Step { address: start, instruction, operands, write, next_address: *address,
+        relative_base: memory.relative_base };
+    observer.after_instruction(&step, memory);
+    Ok(step)
 }
 
-pub fn execute_intcode<I: Input, O: Output>(memory: &[i64], input: &mut I, output: &mut O) -> Vec<i64> {
+pub fn try_execute_instruction_observed<S: Storage, I: Input<S::Cell>, O: Output<S::Cell>, B: Observer<S::Cell>>(memory: &mut Memory<S>, input: &mut I, output: &mut O, address: &mut usize, observer: &mut B) -> Result<bool, IntcodeError> {
+    let (instruction, params) = fetch_instruction(memory, *address)?;
+    execute_fetched(memory, input, output, address, instruction, params, observer)?;
+    Ok(instruction.opcode != Opcode::Halt)
+}
+
+pub fn try_execute_instruction<S: Storage, I: Input<S::Cell>, O: Output<S::Cell>>(memory: &mut Memory<S>, input: &mut I, output: &mut O, address: &mut usize) -> Result<bool, IntcodeError> {
+    try_execute_instruction_observed(memory, input, output, address, &mut NoObserver)
+}
+
+pub fn execute_instruction<S: Storage, I: Input<S::Cell>, O: Output<S::Cell>>(memory: &mut Memory<S>, input: &mut I, output: &mut O, address: &mut usize) -> bool {
+    match try_execute_instruction(memory, input, output, address) {
+        Ok(running) => running,
+        Err(error) => panic!("{}", error)
+    }
+}
+
+pub fn try_execute_intcode_observed<C: Cell, I: Input<C>, O: Output<C>, B: Observer<C>>(memory: &[C], input: &mut I, output: &mut O, observer: &mut B) -> Result<Vec<C>, IntcodeError> {
     let mut memory = Memory::new(memory);
 
     let mut address = 0;
-    while execute_instruction(&mut memory, input, output, &mut address) {}
+    while try_execute_instruction_observed(&mut memory, input, output, &mut address, observer)? {}
+
+    Ok(memory.into_cells())
+}
+
+// runs a tape to completion on any storage backend and hands back the final memory
+pub fn try_execute_intcode_with<S: Storage, I: Input<S::Cell>, O: Output<S::Cell>>(storage: S, input: &mut I, output: &mut O) -> Result<Memory<S>, IntcodeError> {
+    let mut memory = Memory::from_storage(storage);
+
+    let mut address = 0;
+    while try_execute_instruction(&mut memory, input, output, &mut address)? {}
 
-    memory.memory
+    Ok(memory)
+}
+
+// Memory is dense and grows up to DenseStorage::DEFAULT_LIMIT (2^24) cells. Writes at or above it fail with
+// IntcodeError::AddressOutOfRange, use try_execute_intcode_with and a PagedStorage for tapes that need more.
+pub fn try_execute_intcode<C: Cell, I: Input<C>, O: Output<C>>(memory: &[C], input: &mut I, output: &mut O) -> Result<Vec<C>, IntcodeError> {
+    try_execute_intcode_observed(memory, input, output, &mut NoObserver)
+}
+
+// panics where try_execute_intcode returns an error, including writes at or above DenseStorage::DEFAULT_LIMIT
+pub fn execute_intcode<C: Cell, I: Input<C>, O: Output<C>>(memory: &[C], input: &mut I, output: &mut O) -> Vec<C> {
+    match try_execute_intcode(memory, input, output) {
+        Ok(memory) => memory,
+        Err(error) => panic!("{}", error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status<C: Cell = i64> {
+    Halted,
+    NeedsInput,
+    Output(C),
+    // only returned when the corresponding Limits are set on the machine
+    StepLimit,
+    DeadlineExceeded,
+    InfiniteLoop,
+    // a write changed a watched cell, the machine stops right after the writing instruction
+    Watchpoint(MemoryWrite<C>)
+}
+
+struct QueueInput<'a, C: Cell> {
+    values: &'a mut VecDeque<C>
+}
+
+impl<'a, C: Cell> Input<C> for QueueInput<'a, C> {
+    fn get_next(&mut self) -> C {
+        self.values.pop_front().expect("input queue drained while executing an input instruction")
+    }
+
+    fn try_get_next(&mut self) -> Option<C> {
+        self.values.pop_front()
+    }
+}
+
+struct QueueOutput<'a, C: Cell> {
+    values: &'a mut VecDeque<C>
+}
+
+impl<'a, C: Cell> Output<C> for QueueOutput<'a, C> {
+    fn output(&mut self, value: C) {
+        self.values.push_back(value);
+    }
+}
+
+struct LastOutput<C: Cell> {
+    value: Option<C>
+}
+
+impl<C: Cell> Output<C> for LastOutput<C> {
+    fn output(&mut self, value: C) {
+        self.value = Some(value);
+    }
+}
+
+// a machine that owns its state and pauses whenever it produces an output or runs out of input
+#[derive(Clone, Debug)]
+pub struct Machine<S: Storage = DenseStorage> {
+    memory: Memory<S>,
+    address: usize,
+    inputs: VecDeque<S::Cell>,
+    cache: Option<DecodeCache<S::Cell>>,
+    limits: Limits,
+    steps: u64,
+    loops: Option<LoopDetector<S::Cell>>,
+    map: MemoryMap<S::Cell>,
+    history: Option<UndoLog<S::Cell>>,
+    instructions: InstructionSet<S::Cell>,
+    // outputs of a custom instruction that wrote more than one value
+    outputs: VecDeque<S::Cell>,
+    exit_code: Option<S::Cell>
+}
+
+impl Machine {
+    pub fn new(tape: &[i64]) -> Machine {
+        Machine::from_memory(Memory::new(tape))
+    }
+
+    pub fn with_decode_cache(tape: &[i64]) -> Machine {
+        let mut machine = Machine::new(tape);
+        machine.cache = Some(DecodeCache::for_tape(tape));
+        machine
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { memory: self.memory.cells().to_vec(), address: self.address, relative_base: self.memory.relative_base,
+            inputs: self.inputs.iter().copied().collect(), outputs: self.outputs.iter().copied().collect(),
+            exit_code: self.exit_code, steps: self.steps }
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot) -> Machine {
+        let mut machine = Machine::new(&snapshot.memory);
+        machine.restore(snapshot);
+        machine
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = Memory::new(&snapshot.memory);
+        self.memory.relative_base = snapshot.relative_base;
+        self.address = snapshot.address;
+        self.inputs = snapshot.inputs.iter().copied().collect();
+        self.outputs = snapshot.outputs.iter().copied().collect();
+        self.exit_code = snapshot.exit_code;
+        self.steps = snapshot.steps;
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        if let Some(loops) = &mut self.loops {
+            loops.reset();
+        }
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+}
+
+impl<S: Storage> Machine<S> {
+    pub fn from_memory(memory: Memory<S>) -> Machine<S> {
+        Machine { memory, address: 0, inputs: VecDeque::new(), cache: None, limits: Limits::default(), steps: 0,
+            loops: None, map: MemoryMap::default(), history: None, instructions: InstructionSet::standard(),
+            outputs: VecDeque::new(), exit_code: None }
+    }
+
+    // Opcodes the set runs on the built-in interpreter behave exactly as without a set. Custom instructions are not
+    // passed to observers and can not be stepped back over: they restart loop detection and clear the history.
+    pub fn set_instruction_set(&mut self, instructions: InstructionSet<S::Cell>) {
+        self.instructions = instructions;
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet<S::Cell> {
+        &self.instructions
+    }
+
+    // the code a custom instruction halted with, standard halts have none
+    pub fn exit_code(&self) -> Option<&S::Cell> {
+        self.exit_code.as_ref()
+    }
+
+    // the step limit counts all instructions since the machine was created, not just those of the next run
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.loops = if limits.detect_loops { Some(LoopDetector::new()) } else { None };
+        self.limits = limits;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // keeps the last capacity instructions so that step_back can take them back, a capacity of 0 turns it off
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        match &mut self.history {
+            _ if capacity == 0 => self.history = None,
+            Some(history) => history.set_capacity(capacity),
+            None => self.history = Some(UndoLog::new(capacity))
+        }
+    }
+
+    // Takes back the newest instruction in the history. An input value goes back to the front of the pending
+    // inputs, an output can not be taken back.
+    pub fn step_back(&mut self) -> bool {
+        let undone = match &mut self.history {
+            Some(history) => history.undo(&mut self.memory, &mut self.address),
+            None => None
+        };
+        let undone = match undone {
+            Some(undone) => undone,
+            None => return false
+        };
+        if let Some(write) = undone.write {
+            if let Some(cache) = &mut self.cache {
+                cache.invalidate(write.address);
+            }
+            if undone.opcode == Opcode::Input {
+                self.inputs.push_front(write.new);
+            }
+        }
+        for write in &undone.external {
+            if let Some(cache) = &mut self.cache {
+                cache.invalidate(write.address);
+            }
+        }
+        if let Some(loops) = &mut self.loops {
+            loops.reset();
+        }
+        self.steps -= 1;
+        true
+    }
+
+    pub fn enable_decode_cache(&mut self) {
+        if self.cache.is_none() {
+            self.cache = Some(DecodeCache::new());
+        }
+    }
+
+    pub fn push_input(&mut self, value: S::Cell) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn extend_input(&mut self, values: &[S::Cell]) {
+        self.inputs.extend(values.iter().cloned());
+    }
+
+    pub fn pending_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn memory(&self) -> &Memory<S> {
+        &self.memory
+    }
+
+    // outside writes can hit code, so this drops all cached instructions and restarts loop detection
+    pub fn memory_mut(&mut self) -> &mut Memory<S> {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        if let Some(loops) = &mut self.loops {
+            loops.reset();
+        }
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        &mut self.memory
+    }
+
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    // reads by instructions in the range ask the device first, writes are passed on to it afterwards
+    pub fn map_region<D: Device<S::Cell> + Send + 'static>(&mut self, range: Range<usize>, device: Arc<Mutex<D>>) {
+        self.map.map(range, device);
+    }
+
+    pub fn unmap_region(&mut self, start: usize) -> bool {
+        self.map.unmap(start)
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.map.add_watchpoint(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.map.remove_watchpoint(address)
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.memory.relative_base
+    }
+
+    pub fn run(&mut self) -> Result<Status<S::Cell>, IntcodeError> {
+        self.run_observed(&mut NoObserver)
+    }
+
+    pub fn run_observed<B: Observer<S::Cell>>(&mut self, observer: &mut B) -> Result<Status<S::Cell>, IntcodeError> {
+        loop {
+            if let Some(value) = self.outputs.pop_front() {
+                return Ok(Status::Output(value));
+            }
+            // checked before the next instruction, as a custom instruction can also have queued outputs
+            if let Some(write) = self.map.take_hit() {
+                return Ok(Status::Watchpoint(write));
+            }
+            let builtin = self.instructions.builtin(&self.memory[self.address]);
+            match builtin {
+                Some(Opcode::Halt) => return Ok(Status::Halted),
+                Some(Opcode::Input) if self.inputs.is_empty() => return Ok(Status::NeedsInput),
+                _ => {}
+            }
+            match self.limits.exceeded(self.steps) {
+                Some(Stop::StepLimit) => return Ok(Status::StepLimit),
+                Some(Stop::DeadlineExceeded) => return Ok(Status::DeadlineExceeded),
+                None => {}
+            }
+            if self.loops.as_ref().is_some_and(LoopDetector::detected) {
+                return Ok(Status::InfiniteLoop);
+            }
+            let output = if builtin.is_none() {
+                match self.execute_custom()? {
+                    Some(status) => return Ok(status),
+                    None => self.outputs.pop_front()
+                }
+            } else if self.map.is_empty() {
+                self.execute_unmapped(observer, &[])?
+            } else {
+                let mut map = std::mem::take(&mut self.map);
+                let reads = map.prepare(&mut self.memory, self.address, &self.instructions);
+                for read in &reads {
+                    if let Some(cache) = &mut self.cache {
+                        cache.invalidate(read.address);
+                    }
+                }
+                let result = self.execute_unmapped(&mut (&mut map, &mut *observer), &reads);
+                self.map = map;
+                result?
+            };
+            self.steps += 1;
+            if let Some(value) = output {
+                return Ok(Status::Output(value));
+            }
+        }
+    }
+
+    // Runs an instruction of the instruction set that is not built in. Returns the status to stop with right away,
+    // the outputs are queued.
+    fn execute_custom(&mut self) -> Result<Option<Status<S::Cell>>, IntcodeError> {
+        for read in self.map.prepare(&mut self.memory, self.address, &self.instructions) {
+            if let Some(cache) = &mut self.cache {
+                cache.invalidate(read.address);
+            }
+        }
+        let mut input = QueueInput { values: &mut self.inputs };
+        let mut output = QueueOutput { values: &mut self.outputs };
+        let mut writes = Vec::new();
+        let result = self.instructions.execute_custom(&mut self.memory, &mut input, &mut output, &mut self.address,
+            &mut writes);
+        for write in &writes {
+            if let Some(cache) = &mut self.cache {
+                cache.invalidate(write.address);
+            }
+            self.map.written(write);
+        }
+        if let Some(loops) = &mut self.loops {
+            loops.reset();
+        }
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        let progress = match result {
+            // nothing changed yet, so the instruction can run again once there is input
+            Err(IntcodeError::InputExhausted { .. }) if writes.is_empty() && self.outputs.is_empty() =>
+                return Ok(Some(Status::NeedsInput)),
+            result => result?
+        };
+        match progress {
+            Progress::Running => Ok(None),
+            Progress::Halted(code) => {
+                self.exit_code = code;
+                Ok(Some(Status::Halted))
+            }
+        }
+    }
+
+    // the device reads are passed to the observers as external writes before the instruction runs
+    fn execute_unmapped<B: Observer<S::Cell>>(&mut self, observer: &mut B, reads: &[MemoryWrite<S::Cell>]) -> Result<Option<S::Cell>, IntcodeError> {
+        if self.loops.is_none() && self.history.is_none() && reads.is_empty() {
+            return self.execute_next(observer);
+        }
+        let mut loops = self.loops.take();
+        let mut history = self.history.take();
+        let mut observers = (&mut loops, (&mut history, &mut *observer));
+        for read in reads {
+            observers.external_write(read, &self.memory);
+        }
+        let result = self.execute_next(&mut observers);
+        self.loops = loops;
+        self.history = history;
+        result
+    }
+
+    fn execute_next<B: Observer<S::Cell>>(&mut self, observer: &mut B) -> Result<Option<S::Cell>, IntcodeError> {
+        let mut input = QueueInput { values: &mut self.inputs };
+        let mut output = LastOutput { value: None };
+        match &mut self.cache {
+            Some(cache) => try_execute_instruction_cached(&mut self.memory, cache, &mut input, &mut output,
+                &mut self.address, observer)?,
+            None => try_execute_instruction_observed(&mut self.memory, &mut input, &mut output, &mut self.address,
+                observer)?
+        };
+        Ok(output.value)
+    }
+
+    // the outputs until the machine halts or blocks on input, panics on errors like execute_intcode
+    pub fn outputs(&mut self) -> Outputs<'_, S> {
+        Outputs { machine: self }
+    }
+
+    // runs until the machine halts or blocks on input, collecting all outputs on the way
+    #[allow(clippy::type_complexity)]
+    pub fn run_to_block(&mut self) -> Result<(Vec<S::Cell>, Status<S::Cell>), IntcodeError> {
+        let mut outputs = Vec::new();
+        loop {
+            match self.run()? {
+                Status::Output(value) => outputs.push(value),
+                status => return Ok((outputs, status))
+            }
+        }
+    }
+}
+
+pub struct Outputs<'a, S: Storage = DenseStorage> {
+    machine: &'a mut Machine<S>
+}
+
+impl<'a, S: Storage> Iterator for Outputs<'a, S> {
+    type Item = S::Cell;
+
+    fn next(&mut self) -> Option<S::Cell> {
+        match self.machine.run() {
+            Ok(Status::Output(value)) => Some(value),
+            Ok(_) => None,
+            Err(error) => panic!("{}", error)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -297,6 +1249,34 @@ mod tests {
     use crate::StdInput;
     use crate::StdOutput;
     use crate::VecOutput;
+    use crate::Machine;
+    use crate::Limits;
+    use crate::Status;
+    use crate::try_execute_intcode;
+    use crate::IntcodeError;
+    use crate::FaultState;
+    use crate::VecInput;
+    use crate::try_execute_intcode_observed;
+    use crate::Observer;
+    use crate::Step;
+    use crate::Memory;
+    use crate::MemoryWrite;
+    use crate::Instruction;
+    use crate::Opcode;
+    use crate::Storage;
+    use crate::DenseStorage;
+    use crate::PagedStorage;
+    use crate::try_execute_intcode_with;
+    use crate::BigInt;
+    use crate::Cell;
+    use crate::Checked;
+
+    fn outputs<C: Cell>(tape: &[i64]) -> Vec<C> {
+        let tape: Vec<C> = tape.iter().map(|&value| C::from_i64(value)).collect();
+        let mut out = VecOutput::default();
+        execute_intcode(&tape, &mut VecInput::from(vec![]), &mut out);
+        out.values
+    }
 
     #[test]
     #[should_panic]
@@ -347,6 +1327,9 @@ mod tests {
         let mut out = VecOutput::new();
         execute_intcode(&memory, &mut StdInput, &mut out);
         assert_eq!((out.values[0].abs() as f64).log10() as i64, 15);
+        assert_eq!(outputs::<Checked>(&memory)[0].to_string().len(), 16);
+        assert_eq!(outputs::<i128>(&memory)[0].to_string().len(), 16);
+        assert_eq!(outputs::<BigInt>(&memory)[0].to_string().len(), 16);
     }
 
     #[test]
@@ -355,6 +1338,22 @@ mod tests {
         let mut out = VecOutput::new();
         execute_intcode(&memory, &mut StdInput, &mut out);
         assert_eq!(out.values[0], 1125899906842624);
+        assert_eq!(outputs::<Checked>(&memory), vec![Checked(1125899906842624)]);
+        assert_eq!(outputs::<i128>(&memory), vec![1125899906842624]);
+        assert_eq!(outputs::<BigInt>(&memory), vec![BigInt::from(1125899906842624)]);
+    }
+
+    #[test]
+    fn overflow() {
+        // squares 2^62 and outputs the result; plain i64 uses native arithmetic, so only the wider cells and
+        // Checked are tried
+        let memory = vec![1002, 7, 4611686018427387904, 7, 4, 7, 99, 4611686018427387904];
+        assert_eq!(outputs::<i128>(&memory), vec![1 << 124]);
+        assert_eq!(outputs::<BigInt>(&memory)[0].to_string(), "21267647932558653966460912964485513216");
+        let tape: Vec<Checked> = memory.iter().map(|&value| Checked(value)).collect();
+        let result = try_execute_intcode(&tape, &mut VecInput::from(vec![]), &mut VecOutput::default());
+        assert_eq!(result, Err(IntcodeError::Overflow {
+            state: FaultState { address: 0, instruction: 1002, relative_base: 0 } }));
     }
 
     #[test]
@@ -364,4 +1363,159 @@ mod tests {
         execute_intcode(&memory, &mut StdInput, &mut out);
         assert_eq!(out.values[0], 109);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn machine_pauses_for_input() {
+        let mut machine = Machine::new(&[3,9,1001,9,5,10,4,10,99,0,0]);
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        machine.push_input(37);
+        assert_eq!(machine.run(), Ok(Status::Output(42)));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn machine_feedback_loop() {
+        let tape = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        let mut amps: Vec<Machine> = [9, 8, 7, 6, 5].iter().map(|&phase| {
+            let mut machine = Machine::new(&tape);
+            machine.push_input(phase);
+            machine
+        }).collect();
+        let mut signal = 0;
+        let mut i = 0;
+        loop {
+            amps[i].push_input(signal);
+            match amps[i].run_to_block().unwrap() {
+                (outputs, Status::Halted) if outputs.is_empty() => break,
+                (outputs, _) => signal = *outputs.last().unwrap()
+            }
+            i = (i + 1) % amps.len();
+        }
+        assert_eq!(signal, 139629729);
+    }
+
+    #[test]
+    fn machine_limits() {
+        // outputs a counter, then spins on a flag that is only cleared from the outside
+        let tape = vec![4, 12, 1001, 12, 1, 12, 1005, 13, 6, 1105, 1, 0, 0, 1];
+        let mut machine = Machine::new(&tape);
+        machine.set_limits(Limits { detect_loops: true, max_steps: Some(1000), ..Limits::default() });
+        assert_eq!(machine.run(), Ok(Status::Output(0)));
+        assert_eq!(machine.run(), Ok(Status::InfiniteLoop));
+        assert_eq!(machine.run(), Ok(Status::InfiniteLoop));
+        machine.memory_mut()[13] = 0;
+        assert_eq!(machine.run(), Ok(Status::Output(1)));
+        machine.memory_mut()[13] = 1;
+        assert_eq!(machine.run(), Ok(Status::InfiniteLoop));
+        let steps = machine.steps();
+        machine.set_limits(Limits { max_steps: Some(steps + 10), ..Limits::default() });
+        assert_eq!(machine.run(), Ok(Status::StepLimit));
+        assert_eq!(machine.steps(), steps + 10);
+    }
+
+    #[test]
+    fn invalid_opcode_error() {
+        let memory = vec![1, 0, 0, 3];
+        let result = try_execute_intcode(&memory, &mut StdInput, &mut StdOutput);
+        assert_eq!(result, Err(IntcodeError::InvalidOpcode { opcode: 0,
+            state: FaultState { address: 4, instruction: 0, relative_base: 0 } }));
+    }
+
+    #[test]
+    fn invalid_mode_error() {
+        let memory = vec![109, 3, 301, 0, 0, 0, 99];
+        let result = try_execute_intcode(&memory, &mut StdInput, &mut StdOutput);
+        assert_eq!(result, Err(IntcodeError::InvalidMode { mode: 3,
+            state: FaultState { address: 2, instruction: 301, relative_base: 3 } }));
+    }
+
+    #[test]
+    fn negative_address_error() {
+        let memory = vec![1, -5, 0, 0, 99];
+        let result = try_execute_intcode(&memory, &mut StdInput, &mut StdOutput);
+        assert_eq!(result, Err(IntcodeError::NegativeAddress { target: -5,
+            state: FaultState { address: 0, instruction: 1, relative_base: 0 } }));
+    }
+
+    #[test]
+    fn input_exhausted_error() {
+        let memory = vec![3, 0, 3, 0, 99];
+        let result = try_execute_intcode(&memory, &mut VecInput::new(vec![1]), &mut StdOutput);
+        assert_eq!(result, Err(IntcodeError::InputExhausted {
+            state: FaultState { address: 2, instruction: 3, relative_base: 0 } }));
+    }
+
+    struct TraceObserver {
+        before: Vec<usize>,
+        steps: Vec<Step>
+    }
+
+    impl Observer for TraceObserver {
+        fn before_instruction<S: Storage>(&mut self, address: usize, _instruction: &Instruction, _memory: &Memory<S>) {
+            self.before.push(address);
+        }
+
+        fn after_instruction<S: Storage>(&mut self, step: &Step, _memory: &Memory<S>) {
+            self.steps.push(*step);
+        }
+    }
+
+    #[test]
+    fn observer() {
+        let memory = vec![109, 5, 22101, 3, 0, 3, 99, 0, 0];
+        let mut observer = TraceObserver { before: Vec::new(), steps: Vec::new() };
+        try_execute_intcode_observed(&memory, &mut StdInput, &mut StdOutput, &mut observer).unwrap();
+        assert_eq!(observer.before, vec![0, 2, 6]);
+        assert_eq!(observer.steps.len(), 3);
+        assert_eq!(observer.steps[0].relative_base, 5);
+        let add = &observer.steps[1];
+        assert_eq!(add.instruction.opcode, Opcode::Add);
+        assert_eq!(add.operands().map(|operand| operand.value).collect::<Vec<_>>(), vec![3, 3, 6]);
+        assert_eq!(add.operand(1).unwrap().address, Some(5));
+        assert_eq!(add.write, Some(MemoryWrite { address: 8, old: 0, new: 6 }));
+        assert_eq!(add.next_address, 6);
+        assert_eq!(observer.steps[2].instruction.opcode, Opcode::Halt);
+    }
+
+    #[test]
+    fn dense_and_paged_storage_agree() {
+        let programs = vec![
+            vec![1, 0, 0, 3, 99],
+            vec![1101, 1, 1, 3, 99],
+            vec![2, 0, 0, 3, 99],
+            vec![1102, 5, 2, 3, 99],
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+            vec![104, 1125899906842624, 99],
+            vec![109, 15, 109, 19, 204, -34, 99],
+            vec![1, 0, 0, 3]
+        ];
+        for program in programs {
+            let mut dense_out = VecOutput::new();
+            let mut paged_out = VecOutput::new();
+            let dense = try_execute_intcode_with(DenseStorage::new(&program), &mut StdInput, &mut dense_out);
+            let paged = try_execute_intcode_with(PagedStorage::new(&program), &mut StdInput, &mut paged_out);
+            assert_eq!(dense_out.values, paged_out.values);
+            match (dense, paged) {
+                (Ok(dense), Ok(paged)) => for (address, value) in dense.cells().iter().enumerate() {
+                    assert_eq!(paged[address], *value);
+                },
+                (dense, paged) => assert_eq!(dense.err(), paged.err())
+            }
+        }
+    }
+
+    #[test]
+    fn huge_addresses() {
+        let tape = vec![109, 1_000_000_000_000, 21101, 7, 0, 0, 204, 0, 99];
+        let mut out = VecOutput::new();
+        let result = try_execute_intcode_with(PagedStorage::new(&tape), &mut StdInput, &mut out).unwrap();
+        assert_eq!(out.values, vec![7]);
+        assert_eq!(result[1_000_000_000_000], 7);
+
+        let result = try_execute_intcode_with(DenseStorage::new(&tape), &mut StdInput, &mut out);
+        assert_eq!(result.err(), Some(IntcodeError::AddressOutOfRange { target: 1_000_000_000_000,
+            state: FaultState { address: 2, instruction: 21101, relative_base: 1_000_000_000_000 } }));
+    }
+}
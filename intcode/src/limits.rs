@@ -0,0 +1,231 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::time::Instant;
+use crate::fault_state;
+use crate::try_execute_instruction_observed;
+use crate::Cell;
+use crate::DenseStorage;
+use crate::FaultState;
+use crate::Input;
+use crate::IntcodeError;
+use crate::Memory;
+use crate::MemoryWrite;
+use crate::NoObserver;
+use crate::Observer;
+use crate::Opcode;
+use crate::Output;
+use crate::Step;
+use crate::Storage;
+
+// the clock is only read every this many instructions
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+// Everything is off by default, so Limits::default() runs like the plain interpreter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub deadline: Option<Instant>,
+    pub detect_loops: bool
+}
+
+impl Limits {
+    // the limit, if any, that keeps the machine from executing another instruction after the given number of steps
+    pub(crate) fn exceeded(&self, steps: u64) -> Option<Stop> {
+        if self.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            return Some(Stop::StepLimit);
+        }
+        if steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Some(Stop::DeadlineExceeded);
+        }
+        None
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Stop {
+    StepLimit,
+    DeadlineExceeded
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome<C: Cell = i64> {
+    Halted(Vec<C>),
+    StepLimit(FaultState),
+    DeadlineExceeded(FaultState),
+    // the machine reached the exact same state twice without any input or output in between
+    InfiniteLoop(FaultState)
+}
+
+fn mix<C: Cell>(address: usize, value: &C) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (address, value).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Brent's cycle detection over the full machine state. Instead of copying memory at every checkpoint it keeps the
+// first old value of every cell written since then, plus a running hash of the difference, so the exact comparison
+// only happens when address, relative base and hash all match. Any input or output starts over.
+#[derive(Clone, Debug, Default)]
+pub struct LoopDetector<C: Cell = i64> {
+    address: usize,
+    relative_base: i64,
+    written: HashMap<usize, C>,
+    difference: u64,
+    since_checkpoint: u64,
+    window: u64,
+    detected: bool
+}
+
+impl<C: Cell> LoopDetector<C> {
+    pub fn new() -> LoopDetector<C> {
+        LoopDetector { window: 1, ..LoopDetector::default() }
+    }
+
+    pub fn detected(&self) -> bool {
+        self.detected
+    }
+
+    pub fn reset(&mut self) {
+        *self = LoopDetector::new();
+    }
+
+    fn checkpoint(&mut self, address: usize, relative_base: i64) {
+        self.address = address;
+        self.relative_base = relative_base;
+        self.written.clear();
+        self.difference = 0;
+        self.since_checkpoint = 0;
+    }
+
+    fn record(&mut self, write: &MemoryWrite<C>) {
+        self.difference = self.difference.wrapping_add(mix(write.address, &write.new))
+            .wrapping_sub(mix(write.address, &write.old));
+        self.written.entry(write.address).or_insert_with(|| write.old.clone());
+    }
+}
+
+impl<C: Cell> Observer<C> for LoopDetector<C> {
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, memory: &Memory<S>) {
+        if self.detected {
+            return;
+        }
+        if step.instruction.opcode == Opcode::Input || step.instruction.opcode == Opcode::Output {
+            self.window = 1;
+            self.checkpoint(step.next_address, step.relative_base);
+            return;
+        }
+        if let Some(write) = &step.write {
+            self.record(write);
+        }
+        self.since_checkpoint += 1;
+        if step.next_address == self.address && step.relative_base == self.relative_base && self.difference == 0
+            && self.written.iter().all(|(&address, old)| memory[address] == *old) {
+            self.detected = true;
+        } else if self.since_checkpoint == self.window {
+            self.window *= 2;
+            self.checkpoint(step.next_address, step.relative_base);
+        }
+    }
+
+    // a device that answers with new values changes the state just like the program writing it
+    fn external_write<S: Storage<Cell = C>>(&mut self, write: &MemoryWrite<C>, _memory: &Memory<S>) {
+        if !self.detected {
+            self.record(write);
+        }
+    }
+}
+
+fn run_limited<C: Cell, I: Input<C>, O: Output<C>, B: Observer<C>>(memory: &mut Memory<DenseStorage<C>>, input: &mut I, output: &mut O, limits: &Limits, observer: &mut B, looping: fn(&B) -> bool) -> Result<Option<Outcome<C>>, IntcodeError> {
+    let mut address = 0;
+    let mut steps = 0;
+    loop {
+        match limits.exceeded(steps) {
+            Some(Stop::StepLimit) => return Ok(Some(Outcome::StepLimit(fault_state(memory, address)))),
+            Some(Stop::DeadlineExceeded) => return Ok(Some(Outcome::DeadlineExceeded(fault_state(memory, address)))),
+            None => {}
+        }
+        if !try_execute_instruction_observed(memory, input, output, &mut address, observer)? {
+            return Ok(None);
+        }
+        steps += 1;
+        if looping(observer) {
+            return Ok(Some(Outcome::InfiniteLoop(fault_state(memory, address))));
+        }
+    }
+}
+
+// like try_execute_intcode, but stops with a distinct outcome instead of running forever
+pub fn try_execute_intcode_limited<C: Cell, I: Input<C>, O: Output<C>>(memory: &[C], input: &mut I, output: &mut O, limits: &Limits) -> Result<Outcome<C>, IntcodeError> {
+    let mut memory = Memory::new(memory);
+    let stopped = if limits.detect_loops {
+        run_limited(&mut memory, input, output, limits, &mut LoopDetector::new(), LoopDetector::detected)?
+    } else {
+        run_limited(&mut memory, input, output, limits, &mut NoObserver, |_| false)?
+    };
+    Ok(stopped.unwrap_or_else(|| Outcome::Halted(memory.into_cells())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use crate::limits::try_execute_intcode_limited;
+    use crate::limits::Limits;
+    use crate::limits::Outcome;
+    use crate::FaultState;
+    use crate::VecInput;
+    use crate::VecOutput;
+
+    #[test]
+    fn step_limit() {
+        let tape = vec![1101, 1, 2, 5, 99, 0];
+        let limits = Limits { max_steps: Some(1), ..Limits::default() };
+        assert_eq!(try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits),
+            Ok(Outcome::StepLimit(FaultState { address: 4, instruction: 99, relative_base: 0 })));
+        let limits = Limits { max_steps: Some(2), ..Limits::default() };
+        assert_eq!(try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits),
+            Ok(Outcome::Halted(vec![1101, 1, 2, 5, 99, 3])));
+    }
+
+    #[test]
+    fn deadline() {
+        let tape = vec![1105, 1, 0];
+        let limits = Limits { deadline: Some(Instant::now() + Duration::from_millis(10)), ..Limits::default() };
+        match try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits) {
+            Ok(Outcome::DeadlineExceeded(state)) => assert_eq!(state.address, 0),
+            outcome => panic!("unexpected outcome {:?}", outcome)
+        }
+    }
+
+    #[test]
+    fn infinite_loops() {
+        let limits = Limits { detect_loops: true, max_steps: Some(100_000), ..Limits::default() };
+        // jumps back to itself
+        let tape = vec![1105, 1, 0];
+        assert_eq!(try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits),
+            Ok(Outcome::InfiniteLoop(FaultState { address: 0, instruction: 1105, relative_base: 0 })));
+
+        // toggles a cell between 0 and 1 forever
+        let tape = vec![1008, 7, 0, 7, 1105, 1, 0, 0];
+        match try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits) {
+            Ok(Outcome::InfiniteLoop(_)) => {},
+            outcome => panic!("unexpected outcome {:?}", outcome)
+        }
+
+        // counts upwards, every state is new, so only the step limit stops it
+        let tape = vec![1001, 7, 1, 7, 1105, 1, 0, 0];
+        match try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits) {
+            Ok(Outcome::StepLimit(_)) => {},
+            outcome => panic!("unexpected outcome {:?}", outcome)
+        }
+
+        // outputs forever, which is not a loop without I/O
+        let tape = vec![104, 1, 1105, 1, 0];
+        match try_execute_intcode_limited(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new(), &limits) {
+            Ok(Outcome::StepLimit(_)) => {},
+            outcome => panic!("unexpected outcome {:?}", outcome)
+        }
+    }
+}
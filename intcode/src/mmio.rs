@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::Mutex;
+use crate::cell_address;
+use crate::relative_address;
+use crate::Cell;
+use crate::InstructionSet;
+use crate::Memory;
+use crate::MemoryWrite;
+use crate::Mode;
+use crate::Observer;
+use crate::Step;
+use crate::Storage;
+
+// The host side of a memory-mapped address range. Both methods get the absolute address.
+pub trait Device<C: Cell = i64> {
+    // the value an instruction sees when it reads the address; None leaves the value in memory as it is
+    fn read(&mut self, _address: usize) -> Option<C> {
+        None
+    }
+
+    // called after an instruction wrote the address, the new value is already in memory
+    fn write(&mut self, _address: usize, _value: &C) {}
+}
+
+pub type SharedDevice<C> = Arc<Mutex<dyn Device<C> + Send>>;
+
+// Mapped regions and watchpoints of a Machine. Devices are shared, so a cloned machine talks to the same devices,
+// and the host can keep its own handle to look at their state. They are behind a mutex so that a machine with
+// mapped regions can still be moved to another thread.
+#[derive(Clone)]
+pub struct MemoryMap<C: Cell = i64> {
+    regions: Vec<(Range<usize>, SharedDevice<C>)>,
+    watchpoints: BTreeSet<usize>,
+    hit: Option<MemoryWrite<C>>
+}
+
+impl<C: Cell> Default for MemoryMap<C> {
+    fn default() -> Self {
+        MemoryMap { regions: Vec::new(), watchpoints: BTreeSet::new(), hit: None }
+    }
+}
+
+impl<C: Cell> fmt::Debug for MemoryMap<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let regions: Vec<&Range<usize>> = self.regions.iter().map(|(range, _)| range).collect();
+        f.debug_struct("MemoryMap").field("regions", &regions).field("watchpoints", &self.watchpoints)
+            .field("hit", &self.hit).finish()
+    }
+}
+
+impl<C: Cell> MemoryMap<C> {
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty() && self.watchpoints.is_empty()
+    }
+
+    // later regions take precedence where they overlap earlier ones
+    pub fn map(&mut self, range: Range<usize>, device: SharedDevice<C>) {
+        self.regions.push((range, device));
+    }
+
+    // removes all regions that start at the given address
+    pub fn unmap(&mut self, start: usize) -> bool {
+        let count = self.regions.len();
+        self.regions.retain(|(range, _)| range.start != start);
+        count != self.regions.len()
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    fn device(&self, address: usize) -> Option<&SharedDevice<C>> {
+        self.regions.iter().rev().find(|(range, _)| range.contains(&address)).map(|(_, device)| device)
+    }
+
+    // Asks the devices for the cells the instruction at the given address is about to read and stores their
+    // answers, returning the cells that changed. Errors are left to the interpreter to report.
+    pub(crate) fn prepare<S: Storage<Cell = C>>(&self, memory: &mut Memory<S>, address: usize, instructions: &InstructionSet<C>) -> Vec<MemoryWrite<C>> {
+        let mut changed = Vec::new();
+        if self.regions.is_empty() {
+            return changed;
+        }
+        for (mode, raw) in instructions.read_operands(memory, address) {
+            let target = match mode {
+                Mode::Position => cell_address(memory, address, &raw).ok(),
+                Mode::Relative => relative_address(memory, address, &raw).ok(),
+                Mode::Immediate => None
+            };
+            let value = target.and_then(|target| self.device(target).and_then(|device| device.lock().unwrap().read(target)));
+            if let (Some(target), Some(new)) = (target, value) {
+                let old = memory[target].clone();
+                if old != new && memory.write(target, new.clone()) {
+                    changed.push(MemoryWrite { address: target, old, new });
+                }
+            }
+        }
+        changed
+    }
+
+    // passes a write of the program on to its device and checks the watchpoints
+    pub(crate) fn written(&mut self, write: &MemoryWrite<C>) {
+        if let Some(device) = self.device(write.address) {
+            device.lock().unwrap().write(write.address, &write.new);
+        }
+        if write.old != write.new && self.watchpoints.contains(&write.address) {
+            self.hit = Some(write.clone());
+        }
+    }
+
+    // the write that triggered a watchpoint since the last call
+    pub(crate) fn take_hit(&mut self) -> Option<MemoryWrite<C>> {
+        self.hit.take()
+    }
+}
+
+impl<C: Cell> Observer<C> for MemoryMap<C> {
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, _memory: &Memory<S>) {
+        if let Some(write) = &step.write {
+            self.written(write);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+    use crate::mmio::Device;
+    use crate::Limits;
+    use crate::Machine;
+    use crate::MemoryWrite;
+    use crate::Status;
+
+    // counts up on every read and records every write
+    #[derive(Default)]
+    struct Port {
+        reads: i64,
+        writes: Vec<(usize, i64)>
+    }
+
+    impl Device for Port {
+        fn read(&mut self, _address: usize) -> Option<i64> {
+            self.reads += 1;
+            Some(self.reads * 10)
+        }
+
+        fn write(&mut self, address: usize, value: &i64) {
+            self.writes.push((address, *value));
+        }
+    }
+
+    #[test]
+    fn mapped_regions() {
+        // prints 100 + [100] twice, then stores 100 + [100] in 101
+        let tape = vec![1001, 100, 100, 50, 4, 50, 1001, 100, 100, 50, 4, 50, 1001, 100, 100, 101, 99];
+        let port = Arc::new(Mutex::new(Port::default()));
+        let mut machine = Machine::new(&tape);
+        machine.map_region(100..102, port.clone());
+        assert_eq!(machine.run_to_block(), Ok((vec![110, 120], Status::Halted)));
+        assert_eq!(port.lock().unwrap().reads, 3);
+        assert_eq!(port.lock().unwrap().writes, vec![(101, 130)]);
+
+        let mut machine = Machine::new(&tape);
+        machine.map_region(100..102, port.clone());
+        assert!(machine.unmap_region(100));
+        assert_eq!(machine.run_to_block(), Ok((vec![100, 100], Status::Halted)));
+
+        // a machine with mapped regions can still run on another thread
+        let mut machine = Machine::new(&tape);
+        machine.map_region(100..102, port.clone());
+        let outputs = thread::spawn(move || machine.run_to_block()).join().unwrap();
+        assert_eq!(outputs, Ok((vec![140, 150], Status::Halted)));
+        assert_eq!(port.lock().unwrap().writes, vec![(101, 130), (101, 160)]);
+    }
+
+    #[test]
+    fn watchpoints() {
+        // counts cell 20 down from 3 and writes the same value into 21 every time
+        let tape = vec![1001, 20, -1, 20, 1101, 0, 0, 21, 1005, 20, 0, 99, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0];
+        let mut machine = Machine::new(&tape);
+        machine.add_watchpoint(20);
+        machine.add_watchpoint(21);
+        for value in (0..3).rev() {
+            assert_eq!(machine.run(), Ok(Status::Watchpoint(MemoryWrite { address: 20, old: value + 1, new: value })));
+            assert_eq!(machine.address(), 4);
+        }
+        assert_eq!(machine.run(), Ok(Status::Halted));
+
+        let mut machine = Machine::new(&tape);
+        machine.add_watchpoint(20);
+        assert!(machine.remove_watchpoint(20));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    // answers every read with the next lower value until it reaches zero
+    struct Countdown(i64);
+
+    impl Device for Countdown {
+        fn read(&mut self, _address: usize) -> Option<i64> {
+            self.0 = (self.0 - 1).max(0);
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn device_reads_are_writes() {
+        // waits until the device at 100 reads as zero, then prints 7
+        let mut tape = vec![1005, 100, 0, 104, 7, 99];
+        tape.resize(101, 0);
+        let mut machine = Machine::new(&tape);
+        machine.map_region(100..101, Arc::new(Mutex::new(Countdown(4))));
+        machine.set_limits(Limits { detect_loops: true, ..Limits::default() });
+        machine.set_history_capacity(10);
+        assert_eq!(machine.run(), Ok(Status::Output(7)));
+        assert!(machine.step_back());
+        assert!(machine.step_back());
+        assert_eq!((machine.address(), machine.memory()[100]), (0, 1));
+        assert!(machine.step_back());
+        assert_eq!((machine.address(), machine.memory()[100]), (0, 2));
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread;
+use crate::IntcodeError;
+use crate::Machine;
+use crate::Memory;
+use crate::Observer;
+use crate::Status;
+use crate::Step;
+use crate::Storage;
+
+// the value a node reads when it asks for input and nothing is queued
+const NO_PACKET: i64 = -1;
+
+// A packet as written by a node: destination, x and y. The source is the sending node, or None for packets of the
+// host.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub source: Option<usize>,
+    pub destination: i64,
+    pub x: i64,
+    pub y: i64
+}
+
+// where the router sends packets for a destination address
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Route {
+    Node(usize),
+    // every node except the sender
+    Broadcast,
+    Host,
+    Drop
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop
+}
+
+// Everything on the network that is not a node, e.g. the NAT of day 23. The host only runs while the network lock
+// is held, so it never sees two packets at once, even with the threaded scheduler.
+pub trait Host {
+    fn receive(&mut self, packet: Packet) -> Control;
+
+    // called when the whole network is idle; the returned packets are routed like any other, none ends the run
+    fn idle(&mut self) -> Vec<Packet> {
+        Vec::new()
+    }
+}
+
+impl<F: FnMut(Packet) -> Control> Host for F {
+    fn receive(&mut self, packet: Packet) -> Control {
+        self(packet)
+    }
+}
+
+// Keeps the last packet it received and sends it to one node whenever the network is idle. It stops the network
+// instead of sending the same y value twice in a row.
+#[derive(Clone, Debug, Default)]
+pub struct Nat {
+    address: i64,
+    last: Option<Packet>,
+    delivered: Vec<Packet>
+}
+
+impl Nat {
+    pub fn new(address: i64) -> Nat {
+        Nat { address, ..Nat::default() }
+    }
+
+    pub fn last(&self) -> Option<Packet> {
+        self.last
+    }
+
+    // the packets sent on idle, in order
+    pub fn delivered(&self) -> &[Packet] {
+        &self.delivered
+    }
+}
+
+impl Host for Nat {
+    fn receive(&mut self, packet: Packet) -> Control {
+        self.last = Some(packet);
+        Control::Continue
+    }
+
+    fn idle(&mut self) -> Vec<Packet> {
+        let packet = match self.last {
+            Some(last) => Packet { source: None, destination: self.address, ..last },
+            None => return Vec::new()
+        };
+        if self.delivered.last().is_some_and(|delivered| delivered.y == packet.y) {
+            return Vec::new();
+        }
+        self.delivered.push(packet);
+        vec![packet]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkStatus {
+    // the host returned Control::Stop
+    Stopped,
+    // every node waits for a packet that can never arrive and the host had nothing to send
+    Idle,
+    Halted
+}
+
+// Round robin runs all nodes on the calling thread, one event per node and turn, so runs are reproducible.
+// Threaded gives every node its own thread; the order of packets from different nodes is up to the OS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scheduler {
+    RoundRobin,
+    Threaded
+}
+
+// Remembers the machine state at the last input request that found nothing queued, plus the first old value of
+// every cell written since then. Asking again in the exact same state means the node only spins on empty reads and
+// will do so until a packet arrives.
+#[derive(Clone, Debug, Default)]
+struct IdleTracker {
+    checkpoint: Option<(usize, i64)>,
+    written: HashMap<usize, i64>
+}
+
+impl IdleTracker {
+    fn reset(&mut self) {
+        self.checkpoint = None;
+        self.written.clear();
+    }
+
+    fn poll(&mut self, machine: &Machine) -> bool {
+        let state = (machine.address(), machine.relative_base());
+        if self.checkpoint == Some(state) && self.written.iter().all(|(&address, &old)| machine.memory()[address] == old) {
+            return true;
+        }
+        self.checkpoint = Some(state);
+        self.written.clear();
+        false
+    }
+}
+
+impl Observer for IdleTracker {
+    fn after_instruction<S: Storage<Cell = i64>>(&mut self, step: &Step, _memory: &Memory<S>) {
+        if let (Some(_), Some(write)) = (self.checkpoint, &step.write) {
+            self.written.entry(write.address).or_insert(write.old);
+        }
+    }
+}
+
+enum Event {
+    Sent(Packet),
+    NeedsInput,
+    Halted
+}
+
+struct Node {
+    id: usize,
+    machine: Machine,
+    tracker: IdleTracker,
+    outputs: Vec<i64>,
+    // known to only spin on empty reads, stays set until the next packet
+    idle: bool,
+    halted: bool
+}
+
+impl Node {
+    fn new(tape: &[i64], id: usize) -> Node {
+        let mut machine = Machine::with_decode_cache(tape);
+        machine.push_input(id as i64);
+        Node { id, machine, tracker: IdleTracker::default(), outputs: Vec::new(), idle: false, halted: false }
+    }
+
+    // runs until the node sent a whole packet, needs input or halted
+    fn advance(&mut self) -> Result<Event, IntcodeError> {
+        loop {
+            match self.machine.run_observed(&mut self.tracker)? {
+                Status::Output(value) => {
+                    self.tracker.reset();
+                    self.outputs.push(value);
+                    if self.outputs.len() == 3 {
+                        let packet = Packet { source: Some(self.id), destination: self.outputs[0], x: self.outputs[1],
+                            y: self.outputs[2] };
+                        self.outputs.clear();
+                        return Ok(Event::Sent(packet));
+                    }
+                },
+                Status::NeedsInput => return Ok(Event::NeedsInput),
+                // nodes have no limits or watchpoints, so halting is all that is left
+                _ => {
+                    self.halted = true;
+                    return Ok(Event::Halted);
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self, packet: Packet) {
+        self.machine.extend_input(&[packet.x, packet.y]);
+        self.tracker.reset();
+        self.idle = false;
+    }
+
+    // Answers an input request with an empty queue. Once the node is idle the -1 stays queued while it sleeps; reading
+    // it later leads back to the same state, so the node behaves as if it was run all along.
+    fn receive_nothing(&mut self) -> bool {
+        self.idle = self.tracker.poll(&self.machine);
+        self.machine.push_input(NO_PACKET);
+        self.idle
+    }
+}
+
+struct Shared<'h, H: Host> {
+    inboxes: Vec<VecDeque<Packet>>,
+    // idle or halted
+    waiting: Vec<bool>,
+    halted: Vec<bool>,
+    status: Option<Result<NetworkStatus, IntcodeError>>,
+    host: &'h mut H
+}
+
+// Runs one tape on a number of nodes that talk in packets, like the NICs of day 23. Every node reads its address
+// first and -1 whenever it asks for input and no packet is queued. The network is idle once every node is known to
+// spin on empty reads and no packet is queued, which is exact, not a guess based on counting empty reads.
+#[derive(Clone)]
+pub struct Network {
+    tape: Vec<i64>,
+    size: usize,
+    router: Arc<dyn Fn(i64) -> Route + Send + Sync>,
+    scheduler: Scheduler
+}
+
+impl Network {
+    // addresses of nodes are routed to them, everything else goes to the host
+    pub fn new(tape: &[i64], size: usize) -> Network {
+        let router = move |destination: i64| match destination {
+            destination if destination >= 0 && (destination as usize) < size => Route::Node(destination as usize),
+            _ => Route::Host
+        };
+        Network { tape: tape.to_vec(), size, router: Arc::new(router), scheduler: Scheduler::RoundRobin }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn set_router<F: Fn(i64) -> Route + Send + Sync + 'static>(&mut self, router: F) {
+        self.router = Arc::new(router);
+    }
+
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = scheduler;
+    }
+
+    pub fn run<H: Host + Send>(&self, host: &mut H) -> Result<NetworkStatus, IntcodeError> {
+        match self.scheduler {
+            Scheduler::RoundRobin => self.run_round_robin(host),
+            Scheduler::Threaded => self.run_threaded(host)
+        }
+    }
+
+    fn route<H: Host>(&self, packet: Packet, inboxes: &mut [VecDeque<Packet>], host: &mut H) -> Control {
+        match (self.router)(packet.destination) {
+            Route::Node(id) => {
+                assert!(id < self.size, "packet for {} routed to node {} of {}", packet.destination, id, self.size);
+                inboxes[id].push_back(packet);
+            },
+            Route::Broadcast => for (id, inbox) in inboxes.iter_mut().enumerate() {
+                if packet.source != Some(id) {
+                    inbox.push_back(packet);
+                }
+            },
+            Route::Host => return host.receive(packet),
+            Route::Drop => {}
+        }
+        Control::Continue
+    }
+
+    // routes what the host sends on idle, None if it has nothing
+    fn wake_up<H: Host>(&self, inboxes: &mut [VecDeque<Packet>], host: &mut H) -> Option<NetworkStatus> {
+        let packets = host.idle();
+        if packets.is_empty() {
+            return Some(NetworkStatus::Idle);
+        }
+        for packet in packets {
+            if self.route(packet, inboxes, host) == Control::Stop {
+                return Some(NetworkStatus::Stopped);
+            }
+        }
+        None
+    }
+
+    fn run_round_robin<H: Host>(&self, host: &mut H) -> Result<NetworkStatus, IntcodeError> {
+        let mut nodes: Vec<Node> = (0..self.size).map(|id| Node::new(&self.tape, id)).collect();
+        let mut inboxes = vec![VecDeque::new(); self.size];
+        loop {
+            for node in &mut nodes {
+                let inbox = &mut inboxes[node.id];
+                if node.halted {
+                    inbox.clear();
+                    continue;
+                }
+                if node.idle && inbox.is_empty() {
+                    continue;
+                }
+                match node.advance()? {
+                    Event::Sent(packet) => if self.route(packet, &mut inboxes, host) == Control::Stop {
+                        return Ok(NetworkStatus::Stopped);
+                    },
+                    Event::NeedsInput => match inbox.pop_front() {
+                        Some(packet) => node.receive(packet),
+                        None => { node.receive_nothing(); }
+                    },
+                    Event::Halted => {}
+                }
+            }
+            if nodes.iter().all(|node| node.halted) {
+                return Ok(NetworkStatus::Halted);
+            }
+            if nodes.iter().all(|node| node.halted || (node.idle && inboxes[node.id].is_empty())) {
+                if let Some(status) = self.wake_up(&mut inboxes, host) {
+                    return Ok(status);
+                }
+            }
+        }
+    }
+
+    fn run_threaded<H: Host + Send>(&self, host: &mut H) -> Result<NetworkStatus, IntcodeError> {
+        // without nodes there is no thread to set a status; round robin counts an empty network as halted
+        if self.size == 0 {
+            return Ok(NetworkStatus::Halted);
+        }
+        let shared = Mutex::new(Shared { inboxes: vec![VecDeque::new(); self.size], waiting: vec![false; self.size],
+            halted: vec![false; self.size], status: None, host });
+        let changed = Condvar::new();
+        thread::scope(|scope| {
+            for id in 0..self.size {
+                let (shared, changed) = (&shared, &changed);
+                scope.spawn(move || self.run_node(id, shared, changed));
+            }
+        });
+        shared.into_inner().unwrap().status.expect("all nodes stopped without a network status")
+    }
+
+    fn run_node<H: Host>(&self, id: usize, shared: &Mutex<Shared<H>>, changed: &Condvar) {
+        let mut node = Node::new(&self.tape, id);
+        loop {
+            let event = node.advance();
+            let mut state = shared.lock().unwrap();
+            if state.status.is_some() {
+                return;
+            }
+            match event {
+                Err(error) => {
+                    state.status = Some(Err(error));
+                    changed.notify_all();
+                    return;
+                },
+                Ok(Event::Sent(packet)) => {
+                    let Shared { inboxes, host, .. } = &mut *state;
+                    if self.route(packet, inboxes, *host) == Control::Stop {
+                        state.status = Some(Ok(NetworkStatus::Stopped));
+                    }
+                    changed.notify_all();
+                },
+                Ok(Event::Halted) => {
+                    state.waiting[id] = true;
+                    state.halted[id] = true;
+                    self.check_idle(&mut state, changed);
+                    return;
+                },
+                Ok(Event::NeedsInput) => loop {
+                    if state.status.is_some() {
+                        return;
+                    }
+                    if let Some(packet) = state.inboxes[id].pop_front() {
+                        state.waiting[id] = false;
+                        node.receive(packet);
+                        break;
+                    }
+                    if !node.idle && !node.receive_nothing() {
+                        break;
+                    }
+                    state.waiting[id] = true;
+                    self.check_idle(&mut state, changed);
+                    if state.status.is_none() && state.inboxes[id].is_empty() {
+                        state = changed.wait(state).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_idle<H: Host>(&self, state: &mut Shared<H>, changed: &Condvar) {
+        let idle = (0..self.size).all(|id| state.waiting[id] && (state.halted[id] || state.inboxes[id].is_empty()));
+        if !idle || state.status.is_some() {
+            return;
+        }
+        state.status = if state.halted.iter().all(|&halted| halted) {
+            Some(Ok(NetworkStatus::Halted))
+        } else {
+            self.wake_up(&mut state.inboxes, state.host).map(Ok)
+        };
+        changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::network::Control;
+    use crate::network::Nat;
+    use crate::network::Network;
+    use crate::network::NetworkStatus;
+    use crate::network::Packet;
+    use crate::network::Route;
+    use crate::network::Scheduler;
+
+    // Every node passes packets on to the next one with x increased, the last one sends them to 255. Node 0 starts
+    // the first packet, but only after reading five empty inputs.
+    const RING: &str = "
+                IN -> [id]
+                JT [id], #loop
+        wait:   IN -> [x]
+                ADD [delay], #-1 -> [delay]
+                JT [delay], #wait
+                OUT #1
+                OUT #0
+                OUT #7
+        loop:   IN -> [x]
+                EQ [x], #-1 -> [flag]
+                JT [flag], #loop
+                IN -> [y]
+                ADD [id], #1 -> [target]
+                EQ [target], #4 -> [flag]
+                JF [flag], #send
+                ADD #255, #0 -> [target]
+        send:   OUT [target]
+                ADD [x], #1 -> [x]
+                OUT [x]
+                OUT [y]
+                JT #1, #loop
+        id:     DATA 0
+        x:      DATA 0
+        y:      DATA 0
+        target: DATA 0
+        flag:   DATA 0
+        delay:  DATA 5
+    ";
+
+    #[test]
+    fn nat() {
+        let tape = assemble(RING).unwrap();
+        for &scheduler in &[Scheduler::RoundRobin, Scheduler::Threaded] {
+            let mut network = Network::new(&tape, 4);
+            network.set_scheduler(scheduler);
+            let mut nat = Nat::new(0);
+            // counting empty reads would call the network idle while node 0 is still waiting to start
+            assert_eq!(network.run(&mut nat), Ok(NetworkStatus::Idle));
+            assert_eq!(nat.delivered(), &[Packet { source: None, destination: 0, x: 3, y: 7 }]);
+            assert_eq!(nat.last(), Some(Packet { source: Some(3), destination: 255, x: 7, y: 7 }));
+
+            let mut first = None;
+            let status = network.run(&mut |packet| {
+                first = Some(packet);
+                Control::Stop
+            });
+            assert_eq!((status, first.map(|packet| packet.x)), (Ok(NetworkStatus::Stopped), Some(3)));
+
+            let mut network = Network::new(&tape, 0);
+            network.set_scheduler(scheduler);
+            assert_eq!(network.run(&mut Nat::new(0)), Ok(NetworkStatus::Halted));
+        }
+    }
+
+    #[test]
+    fn broadcast() {
+        let tape = assemble(RING).unwrap();
+        for &scheduler in &[Scheduler::RoundRobin, Scheduler::Threaded] {
+            let mut network = Network::new(&tape, 4);
+            network.set_scheduler(scheduler);
+            // the first packet reaches nodes 1, 2 and 3, which all pass it on
+            network.set_router(|destination| match destination {
+                1 => Route::Broadcast,
+                0 | 2 | 3 => Route::Node(destination as usize),
+                _ => Route::Host
+            });
+            let mut received = Vec::new();
+            let status = network.run(&mut |packet: Packet| {
+                received.push((packet.source, packet.x));
+                Control::Continue
+            });
+            received.sort_unstable();
+            assert_eq!(status, Ok(NetworkStatus::Idle));
+            assert_eq!(received, vec![(Some(3), 1), (Some(3), 2), (Some(3), 3)]);
+        }
+    }
+}
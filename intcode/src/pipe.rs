@@ -0,0 +1,107 @@
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use crate::Cell;
+use crate::Input;
+use crate::Output;
+
+// The writing end of a pipe. Clones write into the same pipe, the pipe ends once all of them are closed or dropped.
+// Values written after the reading end was dropped are discarded.
+#[derive(Clone, Debug)]
+pub struct PipeOutput<C: Cell = i64> {
+    sender: Option<Sender<C>>
+}
+
+impl<C: Cell> PipeOutput<C> {
+    pub fn close(&mut self) {
+        self.sender = None;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_none()
+    }
+}
+
+impl<C: Cell> Output<C> for PipeOutput<C> {
+    fn output(&mut self, value: C) {
+        match &self.sender {
+            Some(sender) => { let _ = sender.send(value); },
+            None => panic!("output to a closed pipe")
+        }
+    }
+}
+
+// The reading end of a pipe. Reads block without spinning until a value arrives; once every writer is gone and all
+// values are read, try_get_next returns None, so an interpreter waiting on a halted machine stops with
+// IntcodeError::InputExhausted instead of hanging.
+#[derive(Debug)]
+pub struct PipeInput<C: Cell = i64> {
+    receiver: Receiver<C>
+}
+
+impl<C: Cell> PipeInput<C> {
+    // the next value if one is ready, without blocking
+    pub fn poll(&mut self) -> Option<C> {
+        self.receiver.try_recv().ok()
+    }
+
+    // reads all remaining values until the pipe ends
+    pub fn drain(&mut self) -> Vec<C> {
+        self.receiver.iter().collect()
+    }
+}
+
+impl<C: Cell> Input<C> for PipeInput<C> {
+    fn get_next(&mut self) -> C {
+        self.try_get_next().expect("pipe closed while waiting for input")
+    }
+
+    fn try_get_next(&mut self) -> Option<C> {
+        self.receiver.recv().ok()
+    }
+}
+
+// a connected pair, everything written to the output can be read from the input in the same order
+pub fn pipe() -> (PipeOutput, PipeInput) {
+    cell_pipe()
+}
+
+// pipe for any cell type; pipe itself is fixed to i64 so that integer literals written to it need no annotation
+pub fn cell_pipe<C: Cell>() -> (PipeOutput<C>, PipeInput<C>) {
+    let (sender, receiver) = mpsc::channel();
+    (PipeOutput { sender: Some(sender) }, PipeInput { receiver })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::pipe::pipe;
+    use crate::try_execute_intcode;
+    use crate::FaultState;
+    use crate::IntcodeError;
+    use crate::Output;
+
+    #[test]
+    fn machines_on_threads() {
+        // adds one to every input until it reads a zero
+        let tape = vec![3, 15, 1006, 15, 14, 1001, 15, 1, 15, 4, 15, 1105, 1, 0, 99, 0];
+        let (mut to_first, mut first_input) = pipe();
+        let (mut first_output, mut second_input) = pipe();
+        let (mut second_output, mut results) = pipe();
+        let first_tape = tape.clone();
+        let first = thread::spawn(move || try_execute_intcode(&first_tape, &mut first_input, &mut first_output));
+        let second = thread::spawn(move || try_execute_intcode(&tape, &mut second_input, &mut second_output));
+        for value in &[5, 7] {
+            to_first.output(*value);
+        }
+        to_first.close();
+        assert!(first.join().unwrap().is_err());
+        // the first machine ended without a zero, so the second one sees its pipe end as well
+        match second.join().unwrap() {
+            Err(IntcodeError::InputExhausted { state: FaultState { address: 0, .. } }) => {},
+            result => panic!("unexpected result {:?}", result)
+        }
+        assert_eq!(results.drain(), vec![7, 9]);
+        assert_eq!(results.poll(), None);
+    }
+}
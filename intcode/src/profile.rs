@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+use crate::Cell;
+use crate::Instruction;
+use crate::Memory;
+use crate::Observer;
+use crate::Opcode;
+use crate::Step;
+use crate::Storage;
+
+// how many of the hottest addresses the report lists
+const HOT_ADDRESSES: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Phase {
+    pub name: String,
+    pub instructions: u64,
+    pub elapsed: Duration
+}
+
+// Collects instruction counts as an observer. One profile can observe any number of machines one after the other;
+// add_instance counts them, and profiles of machines that ran on other threads can be merged. Phases are named
+// sections of the host program, their time is wall time including everything the host does in between.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    instances: u64,
+    by_opcode: HashMap<Opcode, u64>,
+    by_address: HashMap<usize, u64>,
+    relative_base_adjustments: u64,
+    relative_base_range: Option<(i64, i64)>,
+    input_wait: Duration,
+    input_started: Option<Instant>,
+    phases: Vec<Phase>,
+    current_phase: Option<(usize, Instant)>
+}
+
+impl<C: Cell> Observer<C> for Profile {
+    fn before_instruction<S: Storage<Cell = C>>(&mut self, _address: usize, instruction: &Instruction, _memory: &Memory<S>) {
+        if instruction.opcode == Opcode::Input {
+            self.input_started = Some(Instant::now());
+        }
+    }
+
+    fn after_instruction<S: Storage<Cell = C>>(&mut self, step: &Step<C>, _memory: &Memory<S>) {
+        *self.by_opcode.entry(step.instruction.opcode).or_insert(0) += 1;
+        *self.by_address.entry(step.address).or_insert(0) += 1;
+        if let Some((index, _)) = self.current_phase {
+            self.phases[index].instructions += 1;
+        }
+        match step.instruction.opcode {
+            Opcode::Input => if let Some(started) = self.input_started.take() {
+                self.input_wait += started.elapsed();
+            },
+            Opcode::AdjustRelativeBase => {
+                self.relative_base_adjustments += 1;
+                let (low, high) = self.relative_base_range.unwrap_or((step.relative_base, step.relative_base));
+                self.relative_base_range = Some((low.min(step.relative_base), high.max(step.relative_base)));
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Profile {
+    // an empty profile without any instances, call add_instance for every machine it observes
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub fn add_instance(&mut self) {
+        self.instances += 1;
+    }
+
+    pub fn instances(&self) -> u64 {
+        self.instances
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.by_opcode.values().sum()
+    }
+
+    pub fn count(&self, opcode: Opcode) -> u64 {
+        self.by_opcode.get(&opcode).copied().unwrap_or(0)
+    }
+
+    pub fn count_at(&self, address: usize) -> u64 {
+        self.by_address.get(&address).copied().unwrap_or(0)
+    }
+
+    // addresses sorted by how often they were executed, most often first
+    pub fn hot_addresses(&self) -> Vec<(usize, u64)> {
+        let mut addresses: Vec<(usize, u64)> = self.by_address.iter().map(|(&address, &count)| (address, count)).collect();
+        addresses.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses
+    }
+
+    pub fn relative_base_adjustments(&self) -> u64 {
+        self.relative_base_adjustments
+    }
+
+    // lowest and highest relative base after any adjustment
+    pub fn relative_base_range(&self) -> Option<(i64, i64)> {
+        self.relative_base_range
+    }
+
+    // time spent inside input instructions, which is the time blocking inputs waited for a value
+    pub fn input_wait(&self) -> Duration {
+        self.input_wait
+    }
+
+    // ends the current phase and starts or continues the one with the given name
+    pub fn phase(&mut self, name: &str) {
+        self.end_phase();
+        let index = match self.phases.iter().position(|phase| phase.name == name) {
+            Some(index) => index,
+            None => {
+                self.phases.push(Phase { name: name.to_string(), ..Phase::default() });
+                self.phases.len() - 1
+            }
+        };
+        self.current_phase = Some((index, Instant::now()));
+    }
+
+    pub fn end_phase(&mut self) {
+        if let Some((index, started)) = self.current_phase.take() {
+            self.phases[index].elapsed += started.elapsed();
+        }
+    }
+
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    // adds all counts of the other profile, phases with the same name are combined
+    pub fn merge(&mut self, other: &Profile) {
+        self.instances += other.instances;
+        for (&opcode, &count) in &other.by_opcode {
+            *self.by_opcode.entry(opcode).or_insert(0) += count;
+        }
+        for (&address, &count) in &other.by_address {
+            *self.by_address.entry(address).or_insert(0) += count;
+        }
+        self.relative_base_adjustments += other.relative_base_adjustments;
+        self.relative_base_range = match (self.relative_base_range, other.relative_base_range) {
+            (Some((low, high)), Some((other_low, other_high))) => Some((low.min(other_low), high.max(other_high))),
+            (range, None) | (None, range) => range
+        };
+        self.input_wait += other.input_wait;
+        for phase in &other.phases {
+            match self.phases.iter_mut().find(|p| p.name == phase.name) {
+                Some(existing) => {
+                    existing.instructions += phase.instructions;
+                    existing.elapsed += phase.elapsed;
+                },
+                None => self.phases.push(phase.clone())
+            }
+        }
+    }
+
+    // e.g. "day19 executed 12.3 million instructions across 2500 VM instances"
+    pub fn summary(&self, name: &str) -> String {
+        let instructions = self.instructions();
+        let amount = if instructions >= 1_000_000 {
+            format!("{:.1} million", instructions as f64 / 1e6)
+        } else {
+            instructions.to_string()
+        };
+        format!("{} executed {} instructions across {} VM instance{}", name, amount, self.instances,
+            if self.instances == 1 { "" } else { "s" })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let instructions = self.instructions().max(1) as f64;
+        writeln!(f, "{} instructions in {} instances", self.instructions(), self.instances)?;
+        writeln!(f, "by opcode:")?;
+        for opcode in Opcode::all() {
+            let count = self.count(*opcode);
+            writeln!(f, "  {:<4} {:>12} {:>6.2}%", opcode.mnemonic(), count, count as f64 * 100.0 / instructions)?;
+        }
+        writeln!(f, "hottest addresses:")?;
+        for (address, count) in self.hot_addresses().into_iter().take(HOT_ADDRESSES) {
+            writeln!(f, "  {:04} {:>12} {:>6.2}%", address, count, count as f64 * 100.0 / instructions)?;
+        }
+        write!(f, "relative base adjustments: {}", self.relative_base_adjustments)?;
+        match self.relative_base_range {
+            Some((low, high)) => writeln!(f, " (range {}..={})", low, high)?,
+            None => writeln!(f)?
+        }
+        writeln!(f, "input wait: {:?}", self.input_wait)?;
+        if !self.phases.is_empty() {
+            writeln!(f, "phases:")?;
+            for phase in &self.phases {
+                writeln!(f, "  {:<12} {:>12} instructions {:>12?}", phase.name, phase.instructions, phase.elapsed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::profile::Profile;
+    use crate::try_execute_intcode_observed;
+    use crate::Input;
+    use crate::Opcode;
+    use crate::VecOutput;
+
+    struct SlowInput;
+
+    impl Input for SlowInput {
+        fn get_next(&mut self) -> i64 {
+            std::thread::sleep(Duration::from_millis(5));
+            3
+        }
+    }
+
+    // reads n, then outputs n, n - 1, ..., 1 while moving the relative base along
+    const TAPE: [i64; 16] = [3, 15, 4, 15, 109, 1, 1001, 15, -1, 15, 1005, 15, 2, 99, 0, 0];
+
+    #[test]
+    fn counts() {
+        let mut profile = Profile::new();
+        profile.add_instance();
+        profile.phase("run");
+        try_execute_intcode_observed(&TAPE, &mut SlowInput, &mut VecOutput::new(), &mut profile).unwrap();
+        profile.end_phase();
+        assert_eq!(profile.instructions(), 14);
+        assert_eq!(profile.count(Opcode::Output), 3);
+        assert_eq!(profile.count(Opcode::JumpIfTrue), 3);
+        assert_eq!(profile.count(Opcode::Halt), 1);
+        assert_eq!(profile.count_at(2), 3);
+        assert_eq!(profile.hot_addresses()[0], (2, 3));
+        assert_eq!(profile.relative_base_adjustments(), 3);
+        assert_eq!(profile.relative_base_range(), Some((1, 3)));
+        assert!(profile.input_wait() >= Duration::from_millis(5));
+        assert_eq!(profile.phases()[0].instructions, 14);
+        assert!(profile.phases()[0].elapsed >= profile.input_wait());
+
+        let mut total = Profile::default();
+        for _ in 0..2 {
+            total.merge(&profile);
+        }
+        assert_eq!(total.summary("day0"), "day0 executed 28 instructions across 2 VM instances");
+        assert_eq!(total.phases().len(), 1);
+        assert_eq!(total.count(Opcode::AdjustRelativeBase), 6);
+    }
+}
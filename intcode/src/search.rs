@@ -0,0 +1,249 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use crate::try_execute_intcode;
+use crate::IntcodeError;
+use crate::VecInput;
+use crate::VecOutput;
+
+#[derive(Clone, Debug)]
+enum Dimension {
+    Cell(usize, Vec<i64>),
+    Input(Vec<i64>),
+    // every order of the values, read as consecutive inputs
+    Permutation(Vec<i64>)
+}
+
+impl Dimension {
+    fn len(&self) -> usize {
+        match self {
+            Dimension::Cell(_, values) | Dimension::Input(values) => values.len(),
+            Dimension::Permutation(values) => (1..=values.len()).try_fold(1usize, |count, n| count.checked_mul(n))
+                .expect("too many permutations")
+        }
+    }
+}
+
+// the index-th permutation of values in lexicographic order of positions
+fn permutation(values: &[i64], mut index: usize) -> Vec<i64> {
+    let mut pool = values.to_vec();
+    let mut result = Vec::with_capacity(values.len());
+    for remaining in (0..values.len()).rev() {
+        let count: usize = (1..=remaining).product();
+        result.push(pool.remove(index / count));
+        index %= count;
+    }
+    result
+}
+
+// one point of the search space: the cells to patch and the inputs to give
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub index: usize,
+    pub patches: Vec<(usize, i64)>,
+    pub inputs: Vec<i64>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub memory: Vec<i64>,
+    pub outputs: Vec<i64>
+}
+
+impl Candidate {
+    // cells past the end of the tape are added as zeros first, like memory grows on a write
+    pub fn patch(&self, tape: &[i64]) -> Vec<i64> {
+        let mut tape = tape.to_vec();
+        for &(address, value) in &self.patches {
+            if address >= tape.len() {
+                tape.resize(address + 1, 0);
+            }
+            tape[address] = value;
+        }
+        tape
+    }
+
+    // runs the patched tape with the inputs until it halts
+    pub fn run(&self, tape: &[i64]) -> Result<Run, IntcodeError> {
+        let mut output = VecOutput::new();
+        let memory = try_execute_intcode(&self.patch(tape), &mut VecInput::new(self.inputs.clone()), &mut output)?;
+        Ok(Run { memory, outputs: output.values().clone() })
+    }
+}
+
+// Tries every combination of patched cells, input values and input orders on a pool of threads. Candidates are
+// numbered like nested loops over the dimensions in the order they were added, the last one changing fastest. Both
+// searches return the same result however many threads there are: find_first the lowest matching index, find_best
+// the lowest index among the best.
+#[derive(Clone, Debug)]
+pub struct Search {
+    dimensions: Vec<Dimension>,
+    threads: usize
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        Search::new()
+    }
+}
+
+impl Search {
+    // one thread per available core
+    pub fn new() -> Search {
+        Search { dimensions: Vec::new(), threads: thread::available_parallelism().map_or(1, |threads| threads.get()) }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn add_cell<I: IntoIterator<Item = i64>>(&mut self, address: usize, values: I) {
+        self.dimensions.push(Dimension::Cell(address, values.into_iter().collect()));
+    }
+
+    pub fn add_input<I: IntoIterator<Item = i64>>(&mut self, values: I) {
+        self.dimensions.push(Dimension::Input(values.into_iter().collect()));
+    }
+
+    pub fn add_permutation(&mut self, values: &[i64]) {
+        self.dimensions.push(Dimension::Permutation(values.to_vec()));
+    }
+
+    // the number of candidates
+    pub fn len(&self) -> usize {
+        self.dimensions.iter().try_fold(1usize, |count, dimension| count.checked_mul(dimension.len()))
+            .expect("search space too large")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn candidate(&self, index: usize) -> Candidate {
+        let mut digits = vec![0; self.dimensions.len()];
+        let mut rest = index;
+        for (digit, dimension) in digits.iter_mut().zip(&self.dimensions).rev() {
+            *digit = rest % dimension.len();
+            rest /= dimension.len();
+        }
+        let mut candidate = Candidate { index, patches: Vec::new(), inputs: Vec::new() };
+        for (digit, dimension) in digits.into_iter().zip(&self.dimensions) {
+            match dimension {
+                Dimension::Cell(address, values) => candidate.patches.push((*address, values[digit])),
+                Dimension::Input(values) => candidate.inputs.push(values[digit]),
+                Dimension::Permutation(values) => candidate.inputs.extend(permutation(values, digit))
+            }
+        }
+        candidate
+    }
+
+    // Stops handing out candidates above the lowest match found so far, but still finishes all below it, since one
+    // of those might match as well.
+    pub fn find_first<F: Fn(&Candidate) -> bool + Sync>(&self, matches: F) -> Option<Candidate> {
+        let len = self.len();
+        let next = AtomicUsize::new(0);
+        let found = AtomicUsize::new(usize::MAX);
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= len || index > found.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if matches(&self.candidate(index)) {
+                        found.fetch_min(index, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        match found.into_inner() {
+            usize::MAX => None,
+            index => Some(self.candidate(index))
+        }
+    }
+
+    // the candidate with the highest score, candidates without one are skipped
+    pub fn find_best<K: Ord + Send, F: Fn(&Candidate) -> Option<K> + Sync>(&self, score: F) -> Option<(Candidate, K)> {
+        let len = self.len();
+        let next = AtomicUsize::new(0);
+        let best = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads).map(|_| scope.spawn(|| {
+                let mut best: Option<(usize, K)> = None;
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= len {
+                        return best;
+                    }
+                    if let Some(key) = score(&self.candidate(index)) {
+                        best = better(best, (index, key));
+                    }
+                }
+            })).collect();
+            workers.into_iter().filter_map(|worker| worker.join().unwrap()).fold(None, better)
+        });
+        best.map(|(index, key)| (self.candidate(index), key))
+    }
+}
+
+fn better<K: Ord>(best: Option<(usize, K)>, other: (usize, K)) -> Option<(usize, K)> {
+    match best {
+        Some(best) if best.1 > other.1 || (best.1 == other.1 && best.0 < other.0) => Some(best),
+        _ => Some(other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::search::Search;
+
+    #[test]
+    fn first_patch() {
+        // cell 0 becomes the sum of the cells at the noun and verb
+        let tape = [1, 0, 0, 0, 99, 5, 7, 11];
+        let mut search = Search::new();
+        search.add_cell(1, 0..=7);
+        search.add_cell(2, 0..=7);
+        assert_eq!(search.len(), 64);
+        for &threads in &[1, 8] {
+            search.set_threads(threads);
+            let found = search.find_first(|candidate| candidate.run(&tape).unwrap().memory[0] == 18).unwrap();
+            assert_eq!((found.index, found.patches), (23, vec![(1, 2), (2, 7)]));
+            assert_eq!(search.find_first(|candidate| candidate.run(&tape).unwrap().memory[0] == 1000), None);
+        }
+
+        // the noun may point past the end of the tape, where the patched cell 10 is
+        let mut search = Search::new();
+        search.add_cell(1, 8..=10);
+        search.add_cell(10, vec![30]);
+        let found = search.find_first(|candidate| candidate.run(&tape).unwrap().memory[0] == 31).unwrap();
+        assert_eq!(found.patch(&tape).len(), 11);
+        assert_eq!(found.patches, vec![(1, 10), (10, 30)]);
+    }
+
+    #[test]
+    fn best_permutation() {
+        // outputs the first input minus the second
+        let tape = assemble("
+                    IN -> [a]
+                    IN -> [b]
+                    MUL [b], #-1 -> [b]
+                    ADD [a], [b] -> [a]
+                    OUT [a]
+                    HLT
+            a:      DATA 0
+            b:      DATA 0
+        ").unwrap();
+        let mut search = Search::new();
+        search.add_permutation(&[1, 2, 3, 4]);
+        search.add_input(vec![0, 5]);
+        assert_eq!(search.len(), 48);
+        assert_eq!(search.candidate(3).inputs, vec![1, 2, 4, 3, 5]);
+        for &threads in &[1, 8] {
+            search.set_threads(threads);
+            let (best, difference) = search.find_best(|candidate| candidate.run(&tape).ok()?.outputs.pop()).unwrap();
+            // 4 - 1 appears for four candidates, the first one wins
+            assert_eq!((best.inputs, difference), (vec![4, 1, 2, 3, 0], 3));
+        }
+    }
+}
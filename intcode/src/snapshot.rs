@@ -0,0 +1,178 @@
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+// The complete state of a Machine, including inputs that have been pushed but not consumed yet, outputs of a
+// custom instruction that were not returned yet and the step count the step limit looks at. Settings like the limits
+// or the instruction set are not part of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub memory: Vec<i64>,
+    pub address: usize,
+    pub relative_base: i64,
+    pub inputs: Vec<i64>,
+    pub outputs: Vec<i64>,
+    pub exit_code: Option<i64>,
+    pub steps: u64
+}
+
+fn join(values: &[i64]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_value<T: std::str::FromStr>(text: &str) -> io::Result<T> {
+    text.trim().parse().map_err(|_| invalid_data(format!("invalid value in snapshot: '{}'", text.trim())))
+}
+
+impl Snapshot {
+    // plain text, one field per line:
+    //   intcode-snapshot 1
+    //   address 12
+    //   relative_base 5
+    //   steps 40
+    //   inputs 1,2
+    //   outputs
+    //   exit_code
+    //   memory 1,0,0,3,99
+    // Fields that are missing keep their default, an empty exit_code means the machine has none.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "address {}", self.address)?;
+        writeln!(writer, "relative_base {}", self.relative_base)?;
+        writeln!(writer, "steps {}", self.steps)?;
+        writeln!(writer, "inputs {}", join(&self.inputs))?;
+        writeln!(writer, "outputs {}", join(&self.outputs))?;
+        writeln!(writer, "exit_code {}", self.exit_code.map_or(String::new(), |code| code.to_string()))?;
+        writeln!(writer, "memory {}", join(&self.memory))
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Snapshot> {
+        let mut lines = reader.lines();
+        match lines.next() {
+            Some(Ok(ref header)) if header.trim() == HEADER => {},
+            Some(Err(error)) => return Err(error),
+            _ => return Err(invalid_data(String::from("missing snapshot header")))
+        }
+        let mut snapshot = Snapshot::default();
+        for line in lines {
+            let line = line?;
+            let (key, value) = match line.find(' ') {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => (line.trim(), "")
+            };
+            let list = |value: &str| -> io::Result<Vec<i64>> {
+                value.split(',').filter(|v| !v.trim().is_empty()).map(parse_value).collect()
+            };
+            match key {
+                "address" => snapshot.address = parse_value(value)?,
+                "relative_base" => snapshot.relative_base = parse_value(value)?,
+                "steps" => snapshot.steps = parse_value(value)?,
+                "inputs" => snapshot.inputs = list(value)?,
+                "outputs" => snapshot.outputs = list(value)?,
+                "exit_code" if value.trim().is_empty() => snapshot.exit_code = None,
+                "exit_code" => snapshot.exit_code = Some(parse_value(value)?),
+                "memory" => snapshot.memory = list(value)?,
+                "" => {},
+                _ => return Err(invalid_data(format!("unknown snapshot field '{}'", key)))
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::isa::Context;
+    use crate::isa::Definition;
+    use crate::isa::Effect;
+    use crate::isa::Semantics;
+    use crate::InstructionSet;
+    use crate::IntcodeError;
+    use crate::Machine;
+    use crate::Snapshot;
+    use crate::Status;
+
+    #[test]
+    fn branch_from_snapshot() {
+        // doubles every input until it reads a zero
+        let tape = vec![3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0];
+        let mut machine = Machine::new(&tape);
+        machine.push_input(5);
+        assert_eq!(machine.run(), Ok(Status::Output(10)));
+        let snapshot = machine.snapshot();
+        let mut branch = machine.clone();
+
+        machine.push_input(0);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        branch.push_input(7);
+        assert_eq!(branch.run(), Ok(Status::Output(14)));
+
+        let mut restored = Machine::from_snapshot(&snapshot);
+        restored.push_input(3);
+        assert_eq!(restored.run(), Ok(Status::Output(6)));
+        machine.restore(&snapshot);
+        machine.push_input(1);
+        assert_eq!(machine.run(), Ok(Status::Output(2)));
+    }
+
+    #[test]
+    fn serialization() {
+        let mut machine = Machine::new(&[109, -4, 3, 10, 99]);
+        machine.extend_input(&[1, 2]);
+        let snapshot = machine.snapshot();
+        let mut text = Vec::new();
+        snapshot.write_to(&mut text).unwrap();
+        assert_eq!(String::from_utf8(text.clone()).unwrap(),
+            "intcode-snapshot 1\naddress 0\nrelative_base 0\nsteps 0\ninputs 1,2\noutputs \nexit_code \n\
+            memory 109,-4,3,10,99\n");
+        assert_eq!(Snapshot::read_from(&text[..]).unwrap(), snapshot);
+        assert!(Snapshot::read_from("address 0\n".as_bytes()).is_err());
+        assert!(Snapshot::read_from("intcode-snapshot 1\naddress x\n".as_bytes()).is_err());
+    }
+
+    fn output_pair(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        let (first, second) = (*context.value(0), *context.value(1));
+        context.output(first);
+        context.output(second);
+        Ok(Effect::Continue)
+    }
+
+    fn halt_with_code(context: &mut Context<i64>) -> Result<Effect<i64>, IntcodeError> {
+        Ok(Effect::Halt(Some(*context.value(0))))
+    }
+
+    fn round_trip(snapshot: &Snapshot) -> Snapshot {
+        let mut text = Vec::new();
+        snapshot.write_to(&mut text).unwrap();
+        Snapshot::read_from(&text[..]).unwrap()
+    }
+
+    #[test]
+    fn pending_outputs_and_exit_code() {
+        let mut set = InstructionSet::standard();
+        set.register(Definition { code: 21, mnemonic: "PAIR", arity: 2, writes: Vec::new(),
+            semantics: Semantics::Custom(output_pair) });
+        set.register(Definition { code: 98, mnemonic: "HLTC", arity: 1, writes: Vec::new(),
+            semantics: Semantics::Custom(halt_with_code) });
+        // PAIR #4, #5 and HLTC #7
+        let mut machine = Machine::new(&[1121, 4, 5, 198, 7]);
+        machine.set_instruction_set(set.clone());
+        assert_eq!(machine.run(), Ok(Status::Output(4)));
+        let snapshot = round_trip(&machine.snapshot());
+        assert_eq!((&snapshot.outputs, snapshot.steps), (&vec![5], 1));
+
+        let mut restored = Machine::from_snapshot(&snapshot);
+        restored.set_instruction_set(set);
+        assert_eq!(restored.run_to_block(), Ok((vec![5], Status::Halted)));
+        let snapshot = round_trip(&restored.snapshot());
+        assert_eq!(snapshot.exit_code, Some(7));
+        restored.restore(&snapshot);
+        assert_eq!((restored.exit_code(), restored.steps()), (Some(&7), 1));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use crate::Cell;
+
+// Backing store for the cells of a Memory. Cells that were never written read as zero; cell_mut returns None for
+// addresses the backend refuses to hold, which the interpreter reports as IntcodeError::AddressOutOfRange.
+pub trait Storage {
+    type Cell: Cell;
+
+    fn cell(&self, address: usize) -> Option<&Self::Cell>;
+    fn cell_mut(&mut self, address: usize) -> Option<&mut Self::Cell>;
+}
+
+// a single Vec that grows up to the highest written address, bounded by a limit so that a stray write to a huge
+// address fails instead of trying to allocate all memory below it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenseStorage<C: Cell = i64> {
+    cells: Vec<C>,
+    limit: usize
+}
+
+impl DenseStorage {
+    pub const DEFAULT_LIMIT: usize = 1 << 24;
+}
+
+impl<C: Cell> DenseStorage<C> {
+    pub fn new(tape: &[C]) -> DenseStorage<C> {
+        DenseStorage::with_limit(tape, DenseStorage::DEFAULT_LIMIT)
+    }
+
+    pub fn with_limit(tape: &[C], limit: usize) -> DenseStorage<C> {
+        DenseStorage { cells: tape.to_vec(), limit: limit.max(tape.len()) }
+    }
+
+    pub fn cells(&self) -> &[C] {
+        &self.cells
+    }
+
+    pub fn into_cells(self) -> Vec<C> {
+        self.cells
+    }
+}
+
+impl<C: Cell> Storage for DenseStorage<C> {
+    type Cell = C;
+
+    #[inline]
+    fn cell(&self, address: usize) -> Option<&C> {
+        self.cells.get(address)
+    }
+
+    #[inline]
+    fn cell_mut(&mut self, address: usize) -> Option<&mut C> {
+        if address >= self.cells.len() {
+            if address >= self.limit {
+                return None;
+            }
+            self.cells.resize(address + 1, C::default());
+        }
+        Some(&mut self.cells[address])
+    }
+}
+
+const PAGE_SIZE: usize = 1024;
+
+// Dense cells for low addresses plus lazily allocated fixed-size pages for everything above, so any address up to
+// usize::MAX can be read and written at the cost of one page per touched region.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PagedStorage<C: Cell = i64> {
+    low: Vec<C>,
+    low_limit: usize,
+    pages: HashMap<usize, Box<[C]>>
+}
+
+impl PagedStorage {
+    pub const DEFAULT_LOW_LIMIT: usize = 1 << 16;
+}
+
+impl<C: Cell> PagedStorage<C> {
+    pub fn new(tape: &[C]) -> PagedStorage<C> {
+        PagedStorage { low: tape.to_vec(), low_limit: PagedStorage::DEFAULT_LOW_LIMIT.max(tape.len()),
+            pages: HashMap::new() }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl<C: Cell> Storage for PagedStorage<C> {
+    type Cell = C;
+
+    #[inline]
+    fn cell(&self, address: usize) -> Option<&C> {
+        if address < self.low_limit {
+            self.low.get(address)
+        } else {
+            self.pages.get(&(address / PAGE_SIZE)).map(|page| &page[address % PAGE_SIZE])
+        }
+    }
+
+    #[inline]
+    fn cell_mut(&mut self, address: usize) -> Option<&mut C> {
+        if address < self.low_limit {
+            if address >= self.low.len() {
+                self.low.resize(address + 1, C::default());
+            }
+            Some(&mut self.low[address])
+        } else {
+            let page = self.pages.entry(address / PAGE_SIZE)
+                .or_insert_with(|| vec![C::default(); PAGE_SIZE].into_boxed_slice());
+            Some(&mut page[address % PAGE_SIZE])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::DenseStorage;
+    use crate::storage::PagedStorage;
+    use crate::storage::Storage;
+
+    #[test]
+    fn dense_limit() {
+        let mut storage = DenseStorage::with_limit(&[1i64, 2], 4);
+        assert_eq!(storage.cell(3), None);
+        *storage.cell_mut(3).unwrap() = 5;
+        assert_eq!(storage.cells(), &[1, 2, 0, 5]);
+        assert_eq!(storage.cell_mut(4), None);
+    }
+
+    #[test]
+    fn paged_high_addresses() {
+        let mut storage = PagedStorage::new(&[1i64, 2]);
+        *storage.cell_mut(1_000_000_000_000).unwrap() = 7;
+        *storage.cell_mut(1_000_000_000_001).unwrap() = 8;
+        *storage.cell_mut(usize::MAX).unwrap() = 9;
+        assert_eq!(storage.page_count(), 2);
+        assert_eq!(storage.cell(1_000_000_000_000), Some(&7));
+        assert_eq!(storage.cell(1_000_000_000_002), Some(&0));
+        assert_eq!(storage.cell(2_000_000_000_000), None);
+        assert_eq!(storage.cell(usize::MAX), Some(&9));
+        assert_eq!(storage.cell(1), Some(&2));
+    }
+}
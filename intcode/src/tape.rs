@@ -0,0 +1,322 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::ops::Deref;
+use std::str::FromStr;
+use crate::Cell;
+
+#[derive(Debug)]
+pub enum TapeError {
+    Io(io::Error),
+    // index is the position of the value in the tape, line is 1-based
+    InvalidValue { index: usize, line: usize, token: String },
+    Empty,
+    // binary tapes
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    // a cell that is cut off or does not fit into an i64
+    InvalidCell { index: usize },
+    MissingChecksum,
+    ChecksumMismatch { expected: u32, actual: u32 },
+    TrailingData { offset: usize }
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeError::Io(error) => write!(f, "failed to read tape: {}", error),
+            TapeError::InvalidValue { index, line, token } =>
+                write!(f, "invalid value '{}' at index {} (line {})", token, index, line),
+            TapeError::Empty => write!(f, "tape is empty"),
+            TapeError::InvalidHeader => write!(f, "not a binary tape"),
+            TapeError::UnsupportedVersion(version) => write!(f, "unsupported binary tape version {}", version),
+            TapeError::InvalidCell { index } => write!(f, "invalid cell at index {}", index),
+            TapeError::MissingChecksum => write!(f, "checksum is missing"),
+            TapeError::ChecksumMismatch { expected, actual } =>
+                write!(f, "checksum mismatch: expected {:08x}, got {:08x}", expected, actual),
+            TapeError::TrailingData { offset } => write!(f, "unexpected data after the tape at byte {}", offset)
+        }
+    }
+}
+
+impl error::Error for TapeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TapeError::Io(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for TapeError {
+    fn from(error: io::Error) -> TapeError {
+        TapeError::Io(error)
+    }
+}
+
+// Comma separated values. Whitespace and newlines around values are ignored, so is everything from a '#' to the
+// end of its line, and a single trailing comma.
+pub fn parse_tape<C: Cell>(text: &str) -> Result<Vec<C>, TapeError> {
+    let mut cleaned = String::with_capacity(text.len());
+    for line in text.lines() {
+        cleaned.push_str(line.split('#').next().unwrap_or(""));
+        cleaned.push('\n');
+    }
+    if cleaned.trim().is_empty() {
+        return Err(TapeError::Empty);
+    }
+    let tokens: Vec<&str> = cleaned.split(',').collect();
+    let mut tape = Vec::with_capacity(tokens.len());
+    let mut line = 1;
+    for (index, raw) in tokens.iter().enumerate() {
+        let token = raw.trim();
+        let token_line = line + raw[..raw.len() - raw.trim_start().len()].matches('\n').count();
+        line += raw.matches('\n').count();
+        if token.is_empty() && index > 0 && index == tokens.len() - 1 {
+            break;
+        }
+        tape.push(parse_value(token, index, token_line)?);
+    }
+    Ok(tape)
+}
+
+fn parse_value<C: Cell>(token: &str, index: usize, line: usize) -> Result<C, TapeError> {
+    token.parse().map_err(|_| TapeError::InvalidValue { index, line,
+        token: token.split_whitespace().collect::<Vec<&str>>().join(" ") })
+}
+
+pub fn try_load_tape<C: Cell, R: Read>(mut input: R) -> Result<Vec<C>, TapeError> {
+    let mut text = String::new();
+    input.read_to_string(&mut text)?;
+    parse_tape(&text)
+}
+
+// a parsed tape, mostly for FromStr; it dereferences to the cells, so it can be passed wherever a tape slice goes
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tape<C: Cell = i64>(pub Vec<C>);
+
+impl<C: Cell> FromStr for Tape<C> {
+    type Err = TapeError;
+
+    fn from_str(s: &str) -> Result<Tape<C>, TapeError> {
+        parse_tape(s).map(Tape)
+    }
+}
+
+impl<C: Cell> Deref for Tape<C> {
+    type Target = [C];
+
+    fn deref(&self) -> &[C] {
+        &self.0
+    }
+}
+
+impl<C: Cell> From<Tape<C>> for Vec<C> {
+    fn from(tape: Tape<C>) -> Vec<C> {
+        tape.0
+    }
+}
+
+pub fn save_tape<C: Cell, W: Write>(tape: &[C], mut output: W) -> io::Result<()> {
+    for (i, value) in tape.iter().enumerate() {
+        if i > 0 {
+            write!(output, ",")?;
+        }
+        write!(output, "{}", value)?;
+    }
+    writeln!(output)
+}
+
+// Binary tapes start with the magic bytes, a version and a flags byte, followed by the cell count and the cells as
+// LEB128 varints of their zigzag encoding, so small values of either sign take a single byte. With the checksum
+// flag, an Adler-32 of everything before it follows in little endian.
+pub const BINARY_MAGIC: [u8; 4] = *b"\x7fICT";
+pub const BINARY_VERSION: u8 = 1;
+const CHECKSUM_FLAG: u8 = 1;
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+// None if the varint is cut off or longer than 64 bits
+fn read_varint(bytes: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*offset)?;
+        *offset += 1;
+        if shift == 63 && byte > 1 {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+pub fn is_binary_tape(bytes: &[u8]) -> bool {
+    bytes.starts_with(&BINARY_MAGIC)
+}
+
+pub fn encode_binary_tape(tape: &[i64], checksum: bool) -> Vec<u8> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    bytes.push(BINARY_VERSION);
+    bytes.push(if checksum { CHECKSUM_FLAG } else { 0 });
+    write_varint(&mut bytes, tape.len() as u64);
+    for &value in tape {
+        write_varint(&mut bytes, ((value << 1) ^ (value >> 63)) as u64);
+    }
+    if checksum {
+        let sum = adler32(&bytes);
+        bytes.extend_from_slice(&sum.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn decode_binary_tape(bytes: &[u8]) -> Result<Vec<i64>, TapeError> {
+    if !is_binary_tape(bytes) || bytes.len() < BINARY_MAGIC.len() + 2 {
+        return Err(TapeError::InvalidHeader);
+    }
+    let version = bytes[BINARY_MAGIC.len()];
+    let flags = bytes[BINARY_MAGIC.len() + 1];
+    if version != BINARY_VERSION {
+        return Err(TapeError::UnsupportedVersion(version));
+    }
+    if flags & !CHECKSUM_FLAG != 0 {
+        return Err(TapeError::InvalidHeader);
+    }
+    let mut offset = BINARY_MAGIC.len() + 2;
+    let count = read_varint(bytes, &mut offset).ok_or(TapeError::InvalidHeader)?;
+    let mut tape = Vec::with_capacity((count as usize).min(bytes.len()));
+    for index in 0..count as usize {
+        let zigzag = read_varint(bytes, &mut offset).ok_or(TapeError::InvalidCell { index })?;
+        tape.push((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+    }
+    if flags & CHECKSUM_FLAG != 0 {
+        let stored = bytes.get(offset..offset + 4).ok_or(TapeError::MissingChecksum)?;
+        let expected = u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]);
+        let actual = adler32(&bytes[..offset]);
+        if expected != actual {
+            return Err(TapeError::ChecksumMismatch { expected, actual });
+        }
+        offset += 4;
+    }
+    if offset != bytes.len() {
+        return Err(TapeError::TrailingData { offset });
+    }
+    Ok(tape)
+}
+
+pub fn save_binary_tape<W: Write>(tape: &[i64], mut output: W, checksum: bool) -> io::Result<()> {
+    output.write_all(&encode_binary_tape(tape, checksum))
+}
+
+pub fn try_load_binary_tape<R: Read>(mut input: R) -> Result<Vec<i64>, TapeError> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    decode_binary_tape(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::tape::decode_binary_tape;
+    use crate::tape::encode_binary_tape;
+    use crate::tape::parse_tape;
+    use crate::tape::save_binary_tape;
+    use crate::tape::save_tape;
+    use crate::tape::try_load_binary_tape;
+    use crate::tape::try_load_tape;
+    use crate::tape::Tape;
+    use crate::tape::TapeError;
+    use crate::BigInt;
+
+    fn error(text: &str) -> String {
+        parse_tape::<i64>(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn lenient_format() {
+        assert_eq!(parse_tape::<i64>("1,0,0,3,99\n").unwrap(), vec![1, 0, 0, 3, 99]);
+        let text = "# adds two numbers\n1101, 2,\n  3, 5, # the sum goes here\n99,\r\n\n";
+        assert_eq!(parse_tape::<i64>(text).unwrap(), vec![1101, 2, 3, 5, 99]);
+        assert_eq!(try_load_tape::<i64, _>("104,-7,99".as_bytes()).unwrap(), vec![104, -7, 99]);
+        assert_eq!("1,2".parse::<Tape>().unwrap(), Tape(vec![1, 2]));
+        let tape: Tape<BigInt> = "104,123456789012345678901234567890,99".parse().unwrap();
+        assert_eq!(tape[1].to_string(), "123456789012345678901234567890");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("1,2,x,4"), "invalid value 'x' at index 2 (line 1)");
+        assert_eq!(error("1,2,\n3 4,5"), "invalid value '3 4' at index 2 (line 2)");
+        assert_eq!(error("1,2\n3,4"), "invalid value '2 3' at index 1 (line 1)");
+        assert_eq!(error("1,,2"), "invalid value '' at index 1 (line 1)");
+        assert_eq!(error("1,2,,"), "invalid value '' at index 2 (line 1)");
+        assert_eq!(error("99999999999999999999"), "invalid value '99999999999999999999' at index 0 (line 1)");
+        assert_eq!(error(" # nothing\n"), "tape is empty");
+        match try_load_tape::<i64, _>(&[0xff, 0xfe][..]) {
+            Err(TapeError::Io(_)) => {},
+            result => panic!("unexpected result {:?}", result)
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let tapes = vec![
+            vec![1, 0, 0, 3, 99],
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            vec![104, 1125899906842624, 99],
+            vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+            vec![i64::MIN, i64::MAX, -1, 0, 63, -64, 64, -65],
+            assemble("loop: IN -> [x]\nOUT [x]\nJT [x], #loop\nHLT\nx: DATA 0").unwrap()
+        ];
+        for tape in tapes {
+            for &checksum in &[false, true] {
+                let mut bytes = Vec::new();
+                save_binary_tape(&tape, &mut bytes, checksum).unwrap();
+                assert_eq!(try_load_binary_tape(&bytes[..]).unwrap(), tape);
+            }
+            let mut text = Vec::new();
+            save_tape(&tape, &mut text).unwrap();
+            assert_eq!(try_load_tape::<i64, _>(&text[..]).unwrap(), tape);
+        }
+    }
+
+    #[test]
+    fn binary_format() {
+        assert_eq!(encode_binary_tape(&[1, -1, 99], false), vec![0x7f, b'I', b'C', b'T', 1, 0, 3, 2, 1, 0xc6, 0x01]);
+        let bytes = encode_binary_tape(&[1, -1, 99], true);
+        assert_eq!(bytes.len(), 15);
+
+        let error = |bytes: &[u8]| decode_binary_tape(bytes).unwrap_err().to_string();
+        assert_eq!(error(b"1,2,3"), "not a binary tape");
+        assert_eq!(error(&[0x7f, b'I', b'C', b'T', 2, 0, 0]), "unsupported binary tape version 2");
+        assert_eq!(error(&bytes[..9]), "invalid cell at index 2");
+        assert_eq!(error(&bytes[..12]), "checksum is missing");
+        let mut corrupted = bytes.clone();
+        corrupted[8] = 3;
+        assert!(error(&corrupted).starts_with("checksum mismatch"));
+        let mut longer = bytes;
+        longer.push(0);
+        assert_eq!(error(&longer), "unexpected data after the tape at byte 15");
+        assert_eq!(error(&[0x7f, b'I', b'C', b'T', 1, 0, 1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]),
+            "invalid cell at index 0");
+    }
+}
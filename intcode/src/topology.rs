@@ -0,0 +1,183 @@
+use crate::fault_state;
+use crate::IntcodeError;
+use crate::Machine;
+use crate::Status;
+
+#[derive(Clone, Debug)]
+struct Node {
+    tape: Vec<i64>,
+    inputs: Vec<i64>,
+    targets: Vec<usize>
+}
+
+// Machines wired into a directed graph. Every output of a node is sent to all of its targets, a node with several
+// sources reads their values in the order they were produced. All nodes run on the calling thread, each one until it
+// blocks, in the order they were added, so runs are reproducible.
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    nodes: Vec<Node>,
+    sinks: Vec<usize>
+}
+
+impl Topology {
+    pub fn new() -> Topology {
+        Topology::default()
+    }
+
+    // one node per entry of inputs, each sending its outputs to the next one; the last node is the sink
+    pub fn chain(tape: &[i64], inputs: &[Vec<i64>]) -> Topology {
+        let mut topology = Topology::new();
+        for node_inputs in inputs {
+            let node = topology.add_node(tape, node_inputs);
+            if node > 0 {
+                topology.connect(node - 1, node);
+            }
+        }
+        if let Some(last) = inputs.len().checked_sub(1) {
+            topology.add_sink(last);
+        }
+        topology
+    }
+
+    // a chain whose last node also feeds back into the first one
+    pub fn ring(tape: &[i64], inputs: &[Vec<i64>]) -> Topology {
+        let mut topology = Topology::chain(tape, inputs);
+        if let Some(last) = inputs.len().checked_sub(1) {
+            topology.connect(last, 0);
+        }
+        topology
+    }
+
+    // returns the id of the new node, the inputs are read before anything from other nodes
+    pub fn add_node(&mut self, tape: &[i64], inputs: &[i64]) -> usize {
+        self.nodes.push(Node { tape: tape.to_vec(), inputs: inputs.to_vec(), targets: Vec::new() });
+        self.nodes.len() - 1
+    }
+
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.nodes.len(), "connection to node {} of {}", to, self.nodes.len());
+        self.nodes[from].targets.push(to);
+    }
+
+    // all outputs of a sink are part of the result of run
+    pub fn add_sink(&mut self, node: usize) {
+        assert!(node < self.nodes.len(), "sink {} of {} nodes", node, self.nodes.len());
+        self.sinks.push(node);
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Runs until every node halted and returns the outputs of the sinks in the order they were added. Fails with
+    // IntcodeError::InputExhausted for the first waiting node if the others halted or wait as well.
+    pub fn run(&self) -> Result<Vec<Vec<i64>>, IntcodeError> {
+        let mut machines: Vec<Machine> = self.nodes.iter().map(|node| {
+            let mut machine = Machine::with_decode_cache(&node.tape);
+            machine.extend_input(&node.inputs);
+            machine
+        }).collect();
+        let mut outputs = vec![Vec::new(); self.sinks.len()];
+        let mut halted = vec![false; self.nodes.len()];
+        let mut started = vec![false; self.nodes.len()];
+        loop {
+            let mut progress = false;
+            for id in 0..machines.len() {
+                if halted[id] || (started[id] && machines[id].pending_inputs() == 0) {
+                    continue;
+                }
+                progress = true;
+                started[id] = true;
+                let (values, status) = machines[id].run_to_block()?;
+                halted[id] = status == Status::Halted;
+                for &target in &self.nodes[id].targets {
+                    machines[target].extend_input(&values);
+                }
+                for (sink, sink_outputs) in self.sinks.iter().zip(&mut outputs) {
+                    if *sink == id {
+                        sink_outputs.extend_from_slice(&values);
+                    }
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+        match halted.iter().position(|&halted| !halted) {
+            Some(id) => Err(IntcodeError::InputExhausted {
+                state: fault_state(machines[id].memory(), machines[id].address())
+            }),
+            None => Ok(outputs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::topology::Topology;
+    use crate::FaultState;
+    use crate::IntcodeError;
+
+    // AoC 2019 day 7 examples, amplifiers without and with feedback
+    const CHAIN: [i64; 17] = [3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0];
+    const RING: [i64; 29] = [3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5];
+
+    fn phases(settings: &[i64]) -> Vec<Vec<i64>> {
+        let mut inputs: Vec<Vec<i64>> = settings.iter().map(|&phase| vec![phase]).collect();
+        inputs[0].push(0);
+        inputs
+    }
+
+    #[test]
+    fn amplifiers() {
+        assert_eq!(Topology::chain(&CHAIN, &phases(&[4, 3, 2, 1, 0])).run(), Ok(vec![vec![43210]]));
+        let outputs = Topology::ring(&RING, &phases(&[9, 8, 7, 6, 5])).run().unwrap();
+        assert_eq!(outputs[0].last(), Some(&139629729));
+    }
+
+    #[test]
+    fn fan_out_and_fan_in() {
+        let double = assemble("
+            loop:   IN -> [value]
+                    JF [value], #end
+                    MUL [value], #2 -> [value]
+                    OUT [value]
+                    JT #1, #loop
+            end:    HLT
+            value:  DATA 0
+        ").unwrap();
+        let combine = |opcode| assemble(&format!("
+                    IN -> [a]
+                    IN -> [b]
+                    {} [a], [b] -> [a]
+                    OUT [a]
+                    HLT
+            a:      DATA 0
+            b:      DATA 0
+        ", opcode)).unwrap();
+
+        let mut topology = Topology::new();
+        let source = topology.add_node(&double, &[1, 2, 0]);
+        let sum = topology.add_node(&combine("ADD"), &[]);
+        let product = topology.add_node(&combine("MUL"), &[]);
+        let total = topology.add_node(&combine("ADD"), &[]);
+        for &(from, to) in &[(source, sum), (source, product), (sum, total), (product, total)] {
+            topology.connect(from, to);
+        }
+        topology.add_sink(source);
+        topology.add_sink(total);
+        assert_eq!(topology.run(), Ok(vec![vec![2, 4], vec![14]]));
+
+        let mut topology = Topology::new();
+        topology.add_node(&combine("ADD"), &[1]);
+        assert_eq!(topology.run(), Err(IntcodeError::InputExhausted {
+            state: FaultState { address: 2, instruction: 3, relative_base: 0 }
+        }));
+    }
+}
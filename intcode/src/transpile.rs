@@ -0,0 +1,253 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use crate::disasm::decode_at;
+use crate::disasm::return_sites;
+use crate::try_execute_instruction;
+use crate::Input;
+use crate::Instruction;
+use crate::IntcodeError;
+use crate::Memory;
+use crate::Mode;
+use crate::Opcode;
+use crate::Output;
+
+// Entry point for transpiled programs whenever they can not continue on their own: the program wrote into its
+// own code, jumped to an address that is not the start of a known block, or hit any kind of error.
+pub fn resume<I: Input, O: Output>(mut memory: Memory, relative_base: i64, address: usize, input: &mut I, output: &mut O) -> Result<Vec<i64>, IntcodeError> {
+    memory.set_relative_base(relative_base);
+    let mut address = address;
+    while try_execute_instruction(&mut memory, input, output, &mut address)? {}
+    Ok(memory.into_cells())
+}
+
+struct Program {
+    instructions: Vec<Option<(Instruction, Vec<i64>)>>,
+    leaders: BTreeSet<usize>
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+    opcode == Opcode::JumpIfTrue || opcode == Opcode::JumpIfFalse
+}
+
+// Walks the code from address 0 and splits it into basic blocks. Besides immediate jump targets, return addresses
+// are treated as entry points as well, see disasm::return_sites.
+fn analyze(tape: &[i64]) -> Program {
+    let mut instructions = vec![None; tape.len()];
+    let mut leaders = BTreeSet::new();
+    let mut to_visit = vec![0];
+    leaders.insert(0);
+    loop {
+        while let Some(address) = to_visit.pop() {
+            if address >= tape.len() || instructions[address].is_some() {
+                continue;
+            }
+            let (instruction, params) = match decode_at(tape, address) {
+                Some(decoded) => decoded,
+                None => continue
+            };
+            let next = address + instruction.len();
+            if is_jump(instruction.opcode) {
+                leaders.insert(next);
+                if instruction.modes[1] == Mode::Immediate && params[1] >= 0 {
+                    leaders.insert(params[1] as usize);
+                    to_visit.push(params[1] as usize);
+                }
+            }
+            if instruction.opcode != Opcode::Halt {
+                to_visit.push(next);
+            }
+            instructions[address] = Some((instruction, params));
+        }
+        let is_start: Vec<bool> = instructions.iter().map(Option::is_some).collect();
+        let new_entries = return_sites(tape, &is_start);
+        if new_entries.is_empty() {
+            break;
+        }
+        to_visit.extend(new_entries);
+    }
+    leaders.retain(|&address| address < tape.len() && instructions[address].is_some());
+    Program { instructions, leaders }
+}
+
+fn read(mode: Mode, value: i64, address: usize) -> Option<String> {
+    match mode {
+        Mode::Position if value < 0 => None,
+        Mode::Position => Some(format!("memory[{}]", value)),
+        Mode::Immediate => Some(format!("{}i64", value)),
+        Mode::Relative => Some(format!("memory[rel!({}, {})]", value, address))
+    }
+}
+
+fn target(mode: Mode, value: i64, address: usize) -> Option<String> {
+    match mode {
+        Mode::Relative => Some(format!("rel!({}, {})", value, address)),
+        _ if value < 0 => None,
+        _ => Some(format!("{}usize", value))
+    }
+}
+
+fn emit_instruction(out: &mut String, address: usize, instruction: &Instruction, params: &[i64]) {
+    let indent = "                ";
+    let next = address + instruction.len();
+    let [mode1, mode2, mode3] = instruction.modes;
+    let operands = match instruction.opcode {
+        Opcode::Halt => Some(Vec::new()),
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals =>
+            vec![read(mode1, params[0], address), read(mode2, params[1], address), target(mode3, params[2], address)]
+                .into_iter().collect(),
+        Opcode::Input => vec![target(mode1, params[0], address)].into_iter().collect(),
+        Opcode::Output | Opcode::AdjustRelativeBase => vec![read(mode1, params[0], address)].into_iter().collect(),
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse =>
+            vec![read(mode1, params[0], address), read(mode2, params[1], address)].into_iter().collect()
+    };
+    // a negative position is an error, let the interpreter report it
+    let operands = match operands {
+        Some(operands) => operands,
+        None => {
+            writeln!(out, "{}bail!({});", indent, address).unwrap();
+            return;
+        }
+    };
+    match instruction.opcode {
+        Opcode::Halt => writeln!(out, "{}return Ok(memory.into_cells());", indent).unwrap(),
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+            let result = match instruction.opcode {
+                Opcode::Add => "a + b",
+                Opcode::Multiply => "a * b",
+                Opcode::LessThan => "if a < b { 1 } else { 0 }",
+                _ => "if a == b { 1 } else { 0 }"
+            };
+            writeln!(out, "{}let a = {};", indent, operands[0]).unwrap();
+            writeln!(out, "{}let b = {};", indent, operands[1]).unwrap();
+            writeln!(out, "{}store!({}, {}, {}, {});", indent, operands[2], result, address, next).unwrap();
+        },
+        Opcode::Input => {
+            writeln!(out, "{}let t = {};", indent, operands[0]).unwrap();
+            writeln!(out, "{}let v = match input.try_get_next() {{ Some(v) => v, None => bail!({}) }};", indent,
+                address).unwrap();
+            writeln!(out, "{}store!(t, v, {}, {});", indent, address, next).unwrap();
+        },
+        Opcode::Output => writeln!(out, "{}output.output({});", indent, operands[0]).unwrap(),
+        Opcode::AdjustRelativeBase => writeln!(out, "{}rb = match rb.checked_add({}) {{ Some(rb) => rb, None => bail!({}) }};",
+            indent, operands[0], address).unwrap(),
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let comparison = if instruction.opcode == Opcode::JumpIfTrue { "!=" } else { "==" };
+            writeln!(out, "{}let a = {};", indent, operands[0]).unwrap();
+            writeln!(out, "{}let b = {};", indent, operands[1]).unwrap();
+            writeln!(out, "{}if a {} 0 {{", indent, comparison).unwrap();
+            writeln!(out, "{}    if b < 0 {{ bail!({}); }}", indent, address).unwrap();
+            writeln!(out, "{}    pc = b as usize;", indent).unwrap();
+            writeln!(out, "{}    continue;", indent).unwrap();
+            writeln!(out, "{}}}", indent).unwrap();
+        }
+    }
+}
+
+fn emit_block(out: &mut String, program: &Program, leader: usize) {
+    writeln!(out, "            {} => {{", leader).unwrap();
+    let mut address = leader;
+    loop {
+        let (instruction, params) = program.instructions[address].as_ref().unwrap();
+        emit_instruction(out, address, instruction, params);
+        if instruction.opcode == Opcode::Halt {
+            break;
+        }
+        address += instruction.len();
+        let next_is_code = address < program.instructions.len() && program.instructions[address].is_some();
+        if is_jump(instruction.opcode) || program.leaders.contains(&address) || !next_is_code {
+            writeln!(out, "                pc = {};", address).unwrap();
+            break;
+        }
+    }
+    writeln!(out, "            }},").unwrap();
+}
+
+fn emit_values<T: ToString>(out: &mut String, name: &str, type_name: &str, values: &[T]) {
+    writeln!(out, "const {}: [{}; {}] = [", name, type_name, values.len()).unwrap();
+    for chunk in values.chunks(16) {
+        let line: Vec<String> = chunk.iter().map(|value| value.to_string()).collect();
+        writeln!(out, "    {},", line.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+// Translates a tape into a Rust module with the same execute_intcode/try_execute_intcode signatures as the
+// interpreter. The generated code only runs as long as the code cells match the tape it was generated from and
+// hands over to the interpreter otherwise.
+pub fn transpile(tape: &[i64]) -> String {
+    let program = analyze(tape);
+    let is_code: Vec<bool> = {
+        let mut is_code = vec![false; tape.len()];
+        for (address, decoded) in program.instructions.iter().enumerate() {
+            if let Some((instruction, _)) = decoded {
+                for cell in &mut is_code[address..address + instruction.len()] {
+                    *cell = true;
+                }
+            }
+        }
+        is_code
+    };
+
+    let mut out = String::new();
+    out.push_str("\
+// generated by intcode::transpile::transpile, do not edit
+#![allow(clippy::all, unused, unreachable_code)]
+use intcode::transpile::resume;
+use intcode::Input;
+use intcode::IntcodeError;
+use intcode::Memory;
+use intcode::Output;
+
+");
+    emit_values(&mut out, "TAPE", "i64", tape);
+    emit_values(&mut out, "IS_CODE", "bool", &is_code);
+    out.push_str("
+fn same_code(tape: &[i64]) -> bool {
+    IS_CODE.iter().zip(TAPE.iter()).enumerate().all(|(i, (&is_code, value))| !is_code || tape.get(i) == Some(value))
+}
+
+pub fn try_execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Result<Vec<i64>, IntcodeError> {
+    let mut memory = Memory::new(tape);
+    if !same_code(tape) {
+        return resume(memory, 0, 0, input, output);
+    }
+    let mut rb: i64 = 0;
+    let mut pc: usize = 0;
+    macro_rules! bail {
+        ($at:expr) => { return resume(memory, rb, $at, input, output) }
+    }
+    macro_rules! rel {
+        ($offset:expr, $at:expr) => { match rb.checked_add($offset) { Some(t) if t >= 0 => t as usize, _ => bail!($at) } }
+    }
+    macro_rules! store {
+        ($target:expr, $value:expr, $at:expr, $next:expr) => {
+            let t: usize = $target;
+            let v: i64 = $value;
+            if !memory.write(t, v) {
+                bail!($at);
+            }
+            if IS_CODE.get(t) == Some(&true) {
+                bail!($next);
+            }
+        }
+    }
+    loop {
+        match pc {
+");
+    for leader in &program.leaders {
+        emit_block(&mut out, &program, *leader);
+    }
+    out.push_str("            _ => bail!(pc)
+        }
+    }
+}
+
+pub fn execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Vec<i64> {
+    match try_execute_intcode(tape, input, output) {
+        Ok(memory) => memory,
+        Err(error) => panic!(\"{}\", error)
+    }
+}
+");
+    out
+}
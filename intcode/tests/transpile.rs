@@ -0,0 +1,97 @@
+use std::env;
+use std::fs;
+use intcode::asm::assemble;
+use intcode::execute_intcode;
+use intcode::transpile::transpile;
+use intcode::VecInput;
+use intcode::VecOutput;
+
+mod transpiled;
+
+const CALLS: &str = "
+            ARB #stack
+            IN -> [n]
+    loop:   CALL #double
+            ADD [n], #-1 -> [n]
+            JT [n], #loop
+            HLT
+    double: MUL [n], #2 -> [rb+0]
+            OUT [rb+0]
+            RET
+    n:      DATA 0
+    stack:  DATA 0, 0, 0, 0
+";
+
+fn programs() -> Vec<(&'static str, Vec<i64>)> {
+    vec![
+        ("quine", vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]),
+        ("compare", vec![3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+            1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98,
+            99]),
+        ("relative_base", vec![109, 15, 109, 19, 204, -34, 99]),
+        ("self_modifying", vec![104, 1, 1001, 1, 1, 1, 1007, 1, 5, 18, 1005, 18, 0, 99, 0, 0, 0, 0, 0]),
+        ("calls", assemble(CALLS).unwrap())
+    ]
+}
+
+// set INTCODE_BLESS=1 to regenerate the checked-in modules after changing the transpiler
+#[test]
+fn generated_code_is_up_to_date() {
+    let bless = env::var_os("INTCODE_BLESS").is_some();
+    for (name, tape) in programs() {
+        let path = format!("{}/tests/transpiled/{}.rs", env!("CARGO_MANIFEST_DIR"), name);
+        let code = transpile(&tape);
+        if bless {
+            fs::write(&path, &code).unwrap();
+        } else {
+            assert_eq!(fs::read_to_string(&path).unwrap(), code, "{} is outdated", path);
+        }
+    }
+}
+
+fn tape(name: &str) -> Vec<i64> {
+    programs().into_iter().find(|(n, _)| *n == name).unwrap().1
+}
+
+fn interpret(tape: &[i64], inputs: &[i64]) -> (Vec<i64>, Vec<i64>) {
+    let mut output = VecOutput::new();
+    let memory = execute_intcode(tape, &mut VecInput::new(inputs.to_vec()), &mut output);
+    (memory, output.values().clone())
+}
+
+macro_rules! assert_same {
+    ($module:ident, $tape:expr, $inputs:expr) => {
+        let tape: Vec<i64> = $tape;
+        let mut output = VecOutput::new();
+        let memory = transpiled::$module::execute_intcode(&tape, &mut VecInput::new($inputs.to_vec()), &mut output);
+        assert_eq!((memory, output.values().clone()), interpret(&tape, &$inputs));
+    }
+}
+
+#[test]
+fn same_results_as_interpreter() {
+    assert_same!(quine, tape("quine"), []);
+    for input in 6..11 {
+        assert_same!(compare, tape("compare"), [input]);
+    }
+    assert_same!(relative_base, tape("relative_base"), []);
+    assert_same!(self_modifying, tape("self_modifying"), []);
+    assert_same!(calls, tape("calls"), [4]);
+}
+
+#[test]
+fn falls_back_on_different_code() {
+    // same program, but the first output now reads the input position instead of the immediate 1
+    let mut changed = tape("self_modifying");
+    changed[0] = 4;
+    assert_same!(self_modifying, changed, []);
+}
+
+#[test]
+fn errors_match_interpreter() {
+    let tape = tape("calls");
+    let expected = intcode::try_execute_intcode(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new());
+    let actual = transpiled::calls::try_execute_intcode(&tape, &mut VecInput::new(vec![]), &mut VecOutput::new());
+    assert!(expected.is_err());
+    assert_eq!(actual, expected);
+}
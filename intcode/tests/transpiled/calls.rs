@@ -0,0 +1,113 @@
+// generated by intcode::transpile::transpile, do not edit
+#![allow(clippy::all, unused, unreachable_code)]
+use intcode::transpile::resume;
+use intcode::Input;
+use intcode::IntcodeError;
+use intcode::Memory;
+use intcode::Output;
+
+const TAPE: [i64; 37] = [
+    109, 33, 3, 32, 21101, 13, 0, 0, 109, 1, 1105, 1, 21, 1001, 32, -1,
+    32, 1005, 32, 4, 99, 21002, 32, 2, 0, 204, 0, 109, -1, 2105, 1, 0,
+    0, 0, 0, 0, 0,
+];
+const IS_CODE: [bool; 37] = [
+    true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true,
+    true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true,
+    false, false, false, false, false,
+];
+
+fn same_code(tape: &[i64]) -> bool {
+    IS_CODE.iter().zip(TAPE.iter()).enumerate().all(|(i, (&is_code, value))| !is_code || tape.get(i) == Some(value))
+}
+
+pub fn try_execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Result<Vec<i64>, IntcodeError> {
+    let mut memory = Memory::new(tape);
+    if !same_code(tape) {
+        return resume(memory, 0, 0, input, output);
+    }
+    let mut rb: i64 = 0;
+    let mut pc: usize = 0;
+    macro_rules! bail {
+        ($at:expr) => { return resume(memory, rb, $at, input, output) }
+    }
+    macro_rules! rel {
+        ($offset:expr, $at:expr) => { match rb.checked_add($offset) { Some(t) if t >= 0 => t as usize, _ => bail!($at) } }
+    }
+    macro_rules! store {
+        ($target:expr, $value:expr, $at:expr, $next:expr) => {
+            let t: usize = $target;
+            let v: i64 = $value;
+            if !memory.write(t, v) {
+                bail!($at);
+            }
+            if IS_CODE.get(t) == Some(&true) {
+                bail!($next);
+            }
+        }
+    }
+    loop {
+        match pc {
+            0 => {
+                rb = match rb.checked_add(33i64) { Some(rb) => rb, None => bail!(0) };
+                let t = 32usize;
+                let v = match input.try_get_next() { Some(v) => v, None => bail!(2) };
+                store!(t, v, 2, 4);
+                pc = 4;
+            },
+            4 => {
+                let a = 13i64;
+                let b = 0i64;
+                store!(rel!(0, 4), a + b, 4, 8);
+                rb = match rb.checked_add(1i64) { Some(rb) => rb, None => bail!(8) };
+                let a = 1i64;
+                let b = 21i64;
+                if a != 0 {
+                    if b < 0 { bail!(10); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 13;
+            },
+            13 => {
+                let a = memory[32];
+                let b = -1i64;
+                store!(32usize, a + b, 13, 17);
+                let a = memory[32];
+                let b = 4i64;
+                if a != 0 {
+                    if b < 0 { bail!(17); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 20;
+            },
+            20 => {
+                return Ok(memory.into_cells());
+            },
+            21 => {
+                let a = memory[32];
+                let b = 2i64;
+                store!(rel!(0, 21), a * b, 21, 25);
+                output.output(memory[rel!(0, 25)]);
+                rb = match rb.checked_add(-1i64) { Some(rb) => rb, None => bail!(27) };
+                let a = 1i64;
+                let b = memory[rel!(0, 29)];
+                if a != 0 {
+                    if b < 0 { bail!(29); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 32;
+            },
+            _ => bail!(pc)
+        }
+    }
+}
+
+pub fn execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Vec<i64> {
+    match try_execute_intcode(tape, input, output) {
+        Ok(memory) => memory,
+        Err(error) => panic!("{}", error)
+    }
+}
@@ -0,0 +1,142 @@
+// generated by intcode::transpile::transpile, do not edit
+#![allow(clippy::all, unused, unreachable_code)]
+use intcode::transpile::resume;
+use intcode::Input;
+use intcode::IntcodeError;
+use intcode::Memory;
+use intcode::Output;
+
+const TAPE: [i64; 47] = [
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31,
+    1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104,
+    999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99,
+];
+const IS_CODE: [bool; 47] = [
+    true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true,
+    true, true, true, false, false, false, true, true, true, true, true, true, true, true, true, true,
+    true, true, true, true, true, true, true, true, true, true, true, true, true, false, true,
+];
+
+fn same_code(tape: &[i64]) -> bool {
+    IS_CODE.iter().zip(TAPE.iter()).enumerate().all(|(i, (&is_code, value))| !is_code || tape.get(i) == Some(value))
+}
+
+pub fn try_execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Result<Vec<i64>, IntcodeError> {
+    let mut memory = Memory::new(tape);
+    if !same_code(tape) {
+        return resume(memory, 0, 0, input, output);
+    }
+    let mut rb: i64 = 0;
+    let mut pc: usize = 0;
+    macro_rules! bail {
+        ($at:expr) => { return resume(memory, rb, $at, input, output) }
+    }
+    macro_rules! rel {
+        ($offset:expr, $at:expr) => { match rb.checked_add($offset) { Some(t) if t >= 0 => t as usize, _ => bail!($at) } }
+    }
+    macro_rules! store {
+        ($target:expr, $value:expr, $at:expr, $next:expr) => {
+            let t: usize = $target;
+            let v: i64 = $value;
+            if !memory.write(t, v) {
+                bail!($at);
+            }
+            if IS_CODE.get(t) == Some(&true) {
+                bail!($next);
+            }
+        }
+    }
+    loop {
+        match pc {
+            0 => {
+                let t = 21usize;
+                let v = match input.try_get_next() { Some(v) => v, None => bail!(0) };
+                store!(t, v, 0, 2);
+                let a = memory[21];
+                let b = 8i64;
+                store!(20usize, if a == b { 1 } else { 0 }, 2, 6);
+                let a = memory[20];
+                let b = 22i64;
+                if a != 0 {
+                    if b < 0 { bail!(6); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 9;
+            },
+            9 => {
+                let a = 8i64;
+                let b = memory[21];
+                store!(20usize, if a < b { 1 } else { 0 }, 9, 13);
+                let a = memory[20];
+                let b = 31i64;
+                if a == 0 {
+                    if b < 0 { bail!(13); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 16;
+            },
+            16 => {
+                let a = 0i64;
+                let b = 36i64;
+                if a == 0 {
+                    if b < 0 { bail!(16); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 19;
+            },
+            22 => {
+                let a = memory[21];
+                let b = 125i64;
+                store!(20usize, a * b, 22, 26);
+                output.output(memory[20]);
+                let a = 1i64;
+                let b = 46i64;
+                if a != 0 {
+                    if b < 0 { bail!(28); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 31;
+            },
+            31 => {
+                output.output(999i64);
+                let a = 1i64;
+                let b = 46i64;
+                if a != 0 {
+                    if b < 0 { bail!(33); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 36;
+            },
+            36 => {
+                let a = 1000i64;
+                let b = 1i64;
+                store!(20usize, a + b, 36, 40);
+                output.output(memory[20]);
+                let a = 1i64;
+                let b = 46i64;
+                if a != 0 {
+                    if b < 0 { bail!(42); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 45;
+            },
+            46 => {
+                return Ok(memory.into_cells());
+            },
+            _ => bail!(pc)
+        }
+    }
+}
+
+pub fn execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Vec<i64> {
+    match try_execute_intcode(tape, input, output) {
+        Ok(memory) => memory,
+        Err(error) => panic!("{}", error)
+    }
+}
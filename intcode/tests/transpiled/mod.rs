@@ -0,0 +1,5 @@
+pub mod calls;
+pub mod compare;
+pub mod quine;
+pub mod relative_base;
+pub mod self_modifying;
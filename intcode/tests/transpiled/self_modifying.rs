@@ -0,0 +1,79 @@
+// generated by intcode::transpile::transpile, do not edit
+#![allow(clippy::all, unused, unreachable_code)]
+use intcode::transpile::resume;
+use intcode::Input;
+use intcode::IntcodeError;
+use intcode::Memory;
+use intcode::Output;
+
+const TAPE: [i64; 19] = [
+    104, 1, 1001, 1, 1, 1, 1007, 1, 5, 18, 1005, 18, 0, 99, 0, 0,
+    0, 0, 0,
+];
+const IS_CODE: [bool; 19] = [
+    true, true, true, true, true, true, true, true, true, true, true, true, true, true, false, false,
+    false, false, false,
+];
+
+fn same_code(tape: &[i64]) -> bool {
+    IS_CODE.iter().zip(TAPE.iter()).enumerate().all(|(i, (&is_code, value))| !is_code || tape.get(i) == Some(value))
+}
+
+pub fn try_execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Result<Vec<i64>, IntcodeError> {
+    let mut memory = Memory::new(tape);
+    if !same_code(tape) {
+        return resume(memory, 0, 0, input, output);
+    }
+    let mut rb: i64 = 0;
+    let mut pc: usize = 0;
+    macro_rules! bail {
+        ($at:expr) => { return resume(memory, rb, $at, input, output) }
+    }
+    macro_rules! rel {
+        ($offset:expr, $at:expr) => { match rb.checked_add($offset) { Some(t) if t >= 0 => t as usize, _ => bail!($at) } }
+    }
+    macro_rules! store {
+        ($target:expr, $value:expr, $at:expr, $next:expr) => {
+            let t: usize = $target;
+            let v: i64 = $value;
+            if !memory.write(t, v) {
+                bail!($at);
+            }
+            if IS_CODE.get(t) == Some(&true) {
+                bail!($next);
+            }
+        }
+    }
+    loop {
+        match pc {
+            0 => {
+                output.output(1i64);
+                let a = memory[1];
+                let b = 1i64;
+                store!(1usize, a + b, 2, 6);
+                let a = memory[1];
+                let b = 5i64;
+                store!(18usize, if a < b { 1 } else { 0 }, 6, 10);
+                let a = memory[18];
+                let b = 0i64;
+                if a != 0 {
+                    if b < 0 { bail!(10); }
+                    pc = b as usize;
+                    continue;
+                }
+                pc = 13;
+            },
+            13 => {
+                return Ok(memory.into_cells());
+            },
+            _ => bail!(pc)
+        }
+    }
+}
+
+pub fn execute_intcode<I: Input, O: Output>(tape: &[i64], input: &mut I, output: &mut O) -> Vec<i64> {
+    match try_execute_intcode(tape, input, output) {
+        Ok(memory) => memory,
+        Err(error) => panic!("{}", error)
+    }
+}